@@ -28,13 +28,19 @@ fn main() {
     );
 
     scene.add_shape(
-        Sphere::new(Point3D::new(5.0, 0.0, 0.0), 6.0)
-            .with_material(Material::new(Checker::default()).with_scale(5.0)),
+        Sphere::new(Point3D::new(5.0, 0.0, 0.0), 6.0).with_material(
+            Material::new(Checker::default())
+                .with_scale(5.0)
+                .with_specular(Color::WHITE, 64.0, 0.5),
+        ),
     );
 
     scene.add_shape(
-        Sphere::new(Point3D::new(-5.0, 0.0, 1.0), 2.0)
-            .with_material(Material::new(Checker::new(Color::RED)).with_scale(2.0)),
+        Sphere::new(Point3D::new(-5.0, 0.0, 1.0), 2.0).with_material(
+            Material::new(Checker::new(Color::RED))
+                .with_scale(2.0)
+                .with_specular(Color::WHITE, 32.0, 0.3),
+        ),
     );
 
     scene.add_shape(
@@ -2,7 +2,7 @@ use std::ops::{Add, Mul, Sub};
 
 use image::{Pixel, Rgba};
 
-use crate::{material::Texture, math::Vector3};
+use crate::{material::Texture, math::Vector3, spectrum::rgb_to_spectrum};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
@@ -52,6 +52,19 @@ impl Color {
         Self { r, g, b, a }
     }
 
+    /// For rays tagged with a wavelength, collapse this color to the value of
+    /// its upsampled spectrum at that wavelength (stored in every channel).
+    /// Untagged rays see the color unchanged.
+    pub fn at_wavelength(&self, wavelength: Option<f64>) -> Color {
+        match wavelength {
+            Some(wavelength) => {
+                let v = rgb_to_spectrum(self, wavelength);
+                Color::with_alpha(v, v, v, self.a)
+            }
+            None => *self,
+        }
+    }
+
     pub fn mix(&self, other: impl AsRef<Color>, mix_value: f64) -> Color {
         self * (1.0 - mix_value) + other.as_ref() * mix_value
     }
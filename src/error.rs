@@ -7,11 +7,13 @@ pub type Result<T> = std::result::Result<T, RTError>;
 pub enum RTError {
     Error(String),
     InvalidGeo(String),
+    InvalidIes(String),
 
     ParseFloat(ParseFloatError),
     ParseInt(ParseIntError),
 
     IOError(std::io::Error),
+    Image(image::ImageError),
 }
 
 impl std::error::Error for RTError {
@@ -19,9 +21,11 @@ impl std::error::Error for RTError {
         match *self {
             RTError::Error(_) => None,
             RTError::InvalidGeo(_) => None,
+            RTError::InvalidIes(_) => None,
             RTError::ParseFloat(ref err) => Some(err),
             RTError::ParseInt(ref err) => Some(err),
             RTError::IOError(ref err) => Some(err),
+            RTError::Image(ref err) => Some(err),
         }
     }
 }
@@ -35,9 +39,13 @@ impl std::fmt::Display for RTError {
             RTError::InvalidGeo(ref msg) => {
                 write!(f, "Invalid Geo Mesh: {}", msg)
             }
+            RTError::InvalidIes(ref msg) => {
+                write!(f, "Invalid IES profile: {}", msg)
+            }
             RTError::ParseFloat(ref err) => err.fmt(f),
             RTError::ParseInt(ref err) => err.fmt(f),
             RTError::IOError(ref err) => err.fmt(f),
+            RTError::Image(ref err) => err.fmt(f),
         }
     }
 }
@@ -59,3 +67,9 @@ impl From<std::io::Error> for RTError {
         RTError::IOError(err)
     }
 }
+
+impl From<image::ImageError> for RTError {
+    fn from(err: image::ImageError) -> RTError {
+        RTError::Image(err)
+    }
+}
@@ -12,3 +12,4 @@ pub mod ply;
 pub mod ray;
 pub mod scene;
 pub mod shape;
+pub mod spectrum;
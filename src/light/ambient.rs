@@ -0,0 +1,245 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use crate::{
+    color::Color,
+    math::{Point3D, Vector3},
+};
+
+use super::{orthonormal, Light, Luminous};
+
+/// Darkens ambient light in creases and corners by tracing rays from each
+/// shading point and counting how many hit something within `distance`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Occlusion {
+    pub samples: usize,
+    pub distance: f64,
+}
+
+impl Occlusion {
+    /// Cosine weighted directions around `normal`, so the unoccluded share
+    /// of them is the share of ambient light that arrives.
+    pub fn directions(&self, normal: &Vector3) -> Vec<Vector3> {
+        let mut rng = rand::thread_rng();
+        let (x, y) = orthonormal(normal);
+
+        (0..self.samples)
+            .map(|_| {
+                let r = rng.gen::<f64>().sqrt();
+                let phi = 2.0 * PI * rng.gen::<f64>();
+                let z = (1.0 - r * r).max(0.0).sqrt();
+                x * (r * phi.cos()) + y * (r * phi.sin()) + normal * z
+            })
+            .collect()
+    }
+}
+
+/// The same light arriving from every direction, without shadows. Cheap
+/// fill light for previews, so sides facing away from the other lights
+/// aren't black.
+///
+/// Example:
+/// ```
+/// use raytracer_rs::color::Color;
+/// use raytracer_rs::light::AmbientLight;
+///
+/// let fill = AmbientLight::new()
+///     .color(Color::new(0.8, 0.9, 1.0))
+///     .intensity(0.2)
+///     .ambient_occlusion(16, 2.0);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmbientLight {
+    color: Color,
+    intensity: f64,
+    occlusion: Option<Occlusion>,
+}
+
+impl AmbientLight {
+    pub fn new() -> Self {
+        AmbientLight {
+            color: Color::WHITE,
+            intensity: 0.1,
+            occlusion: None,
+        }
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Trace `samples` rays per shading point to darken the light where
+    /// other shapes are within `distance`.
+    pub fn ambient_occlusion(mut self, samples: usize, distance: f64) -> Self {
+        self.occlusion = Some(Occlusion { samples, distance });
+        self
+    }
+
+    pub fn occlusion(&self) -> Option<Occlusion> {
+        self.occlusion
+    }
+
+    /// The light arriving at a surface, whichever way it faces.
+    pub fn radiance(&self) -> Color {
+        self.color * self.intensity
+    }
+}
+
+impl Default for AmbientLight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Ambient light that blends from a sky color above to a ground color
+/// below, so surfaces are tinted by which way they face.
+///
+/// Example:
+/// ```
+/// use raytracer_rs::color::Color;
+/// use raytracer_rs::light::HemisphereLight;
+///
+/// let outdoors = HemisphereLight::new(Color::new(0.6, 0.7, 1.0), Color::new(0.3, 0.25, 0.2));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct HemisphereLight {
+    sky: Color,
+    ground: Color,
+    up: Vector3,
+    intensity: f64,
+    occlusion: Option<Occlusion>,
+}
+
+impl HemisphereLight {
+    pub fn new(sky: Color, ground: Color) -> Self {
+        HemisphereLight {
+            sky,
+            ground,
+            up: Vector3::J,
+            intensity: 0.2,
+            occlusion: None,
+        }
+    }
+
+    /// The direction the sky is in.
+    pub fn up(mut self, up: Vector3) -> Self {
+        self.up = up.normalize();
+        self
+    }
+
+    pub fn intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Trace `samples` rays per shading point to darken the light where
+    /// other shapes are within `distance`.
+    pub fn ambient_occlusion(mut self, samples: usize, distance: f64) -> Self {
+        self.occlusion = Some(Occlusion { samples, distance });
+        self
+    }
+
+    pub fn occlusion(&self) -> Option<Occlusion> {
+        self.occlusion
+    }
+
+    /// The light arriving at a surface facing `normal`, averaged over the
+    /// parts of the sky and ground it can see.
+    pub fn radiance(&self, normal: &Vector3) -> Color {
+        let t = 0.5 + 0.5 * normal.normalize().dot(self.up);
+        self.ground.mix(self.sky, t) * self.intensity
+    }
+}
+
+impl From<AmbientLight> for Light {
+    fn from(a: AmbientLight) -> Self {
+        Light::Ambient(a)
+    }
+}
+
+impl From<HemisphereLight> for Light {
+    fn from(h: HemisphereLight) -> Self {
+        Light::Hemisphere(h)
+    }
+}
+
+impl Luminous for AmbientLight {
+    fn color(&self) -> Color {
+        self.color
+    }
+
+    fn direction_from(&self, _point: &Point3D) -> Vector3 {
+        Vector3::J
+    }
+
+    fn distance(&self, _point: &Point3D) -> f64 {
+        f64::INFINITY
+    }
+
+    fn intensity(&self) -> f64 {
+        self.intensity
+    }
+
+    fn intensity_at(&self, _point: &Point3D) -> f64 {
+        self.intensity
+    }
+}
+
+impl Luminous for HemisphereLight {
+    fn color(&self) -> Color {
+        self.sky
+    }
+
+    fn direction_from(&self, _point: &Point3D) -> Vector3 {
+        self.up
+    }
+
+    fn distance(&self, _point: &Point3D) -> f64 {
+        f64::INFINITY
+    }
+
+    fn intensity(&self) -> f64 {
+        self.intensity
+    }
+
+    fn intensity_at(&self, _point: &Point3D) -> f64 {
+        self.intensity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hemisphere_blends_by_facing() {
+        let light = HemisphereLight::new(Color::BLUE, Color::RED).intensity(1.0);
+
+        assert_eq!(light.radiance(&Vector3::J), Color::BLUE);
+        assert_eq!(light.radiance(&-Vector3::J), Color::RED);
+        let side = light.radiance(&Vector3::I);
+        assert!((side.r - 0.5).abs() < 1e-9 && (side.b - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn occlusion_rays_leave_the_surface() {
+        let occlusion = Occlusion {
+            samples: 64,
+            distance: 1.0,
+        };
+        let normal = Vector3::new([1.0, 1.0, 0.0]).normalize();
+
+        let directions = occlusion.directions(&normal);
+        assert_eq!(directions.len(), 64);
+        assert!(directions
+            .iter()
+            .all(|d| d.dot(normal) >= 0.0 && (d.magnitude() - 1.0).abs() < 1e-9));
+    }
+}
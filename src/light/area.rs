@@ -0,0 +1,353 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use crate::{
+    color::Color,
+    math::{Point3D, Vector3},
+    ray::Ray,
+    spectrum::Spectrum,
+};
+
+use super::{orthonormal, Light, LightSample, Luminous};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AreaShape {
+    /// A parallelogram spanned by two edges, centered on the light's location
+    /// and emitting on the side `u × v` points to.
+    Rectangle {
+        u: Vector3,
+        v: Vector3,
+    },
+    /// A disk emitting on the side `normal` points to.
+    Disk {
+        normal: Vector3,
+        radius: f64,
+    },
+    Sphere {
+        radius: f64,
+    },
+}
+
+/// A light with a surface, which casts soft shadows. Each shading point
+/// traces several shadow rays spread over the surface of the light, and the
+/// light itself shows up in camera rays.
+///
+/// `intensity` is the total power of the light, so a small area light is as
+/// bright as a point light with the same intensity.
+///
+/// Example:
+/// ```
+/// use raytracer_rs::light::AreaLight;
+/// use raytracer_rs::math::{Point3D, Vector3};
+///
+/// let softbox = AreaLight::rectangle(
+///     Point3D::new(0.0, 10.0, 0.0),
+///     Vector3::new([4.0, 0.0, 0.0]),
+///     Vector3::new([0.0, 0.0, 2.0]),
+/// )
+/// .samples(32);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct AreaLight {
+    location: Point3D,
+    shape: AreaShape,
+    color: Color,
+    intensity: f64,
+    samples: usize,
+    spectrum: Option<Spectrum>,
+}
+
+impl AreaLight {
+    pub fn new(location: Point3D, shape: AreaShape) -> Self {
+        AreaLight {
+            location,
+            shape,
+            color: Color::WHITE,
+            intensity: 3000.0,
+            samples: 16,
+            spectrum: None,
+        }
+    }
+
+    pub fn rectangle(center: Point3D, u: Vector3, v: Vector3) -> Self {
+        Self::new(center, AreaShape::Rectangle { u, v })
+    }
+
+    pub fn disk(center: Point3D, normal: Vector3, radius: f64) -> Self {
+        let normal = normal.normalize();
+        Self::new(center, AreaShape::Disk { normal, radius })
+    }
+
+    pub fn sphere(center: Point3D, radius: f64) -> Self {
+        Self::new(center, AreaShape::Sphere { radius })
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// The number of shadow rays traced towards the light per shading point.
+    pub fn samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    pub fn spectrum(mut self, spectrum: Spectrum) -> Self {
+        self.spectrum = Some(spectrum);
+        self
+    }
+
+    pub fn area(&self) -> f64 {
+        match self.shape {
+            AreaShape::Rectangle { u, v } => u.cross(v).magnitude(),
+            AreaShape::Disk { radius, .. } => PI * radius * radius,
+            AreaShape::Sphere { radius } => 4.0 * PI * radius * radius,
+        }
+    }
+
+    /// The brightness of the surface of the light, relative to its color.
+    pub fn radiance(&self) -> f64 {
+        self.intensity / (PI * self.area())
+    }
+
+    /// Whether `point` is on the emitting side of the light. Spheres emit in
+    /// every direction.
+    pub fn faces(&self, point: &Point3D) -> bool {
+        let normal = match self.shape {
+            AreaShape::Rectangle { u, v } => u.cross(v),
+            AreaShape::Disk { normal, .. } => normal,
+            AreaShape::Sphere { .. } => return true,
+        };
+
+        (point - self.location).dot(normal) > 0.0
+    }
+
+    /// The distance along `ray` to the emitting side of the light, if it's
+    /// hit.
+    pub fn intersect(&self, ray: &Ray) -> Option<f64> {
+        let origin = ray.origin();
+        let direction = ray.direction();
+
+        let planar = |normal: Vector3| {
+            let denom = direction.dot(normal);
+            // only the front face emits
+            if denom >= 0.0 {
+                return None;
+            }
+
+            let t = (self.location - origin).dot(normal) / denom;
+            if t > 1e-6 {
+                Some((t, ray.point_at(t) - self.location))
+            } else {
+                None
+            }
+        };
+
+        match self.shape {
+            AreaShape::Rectangle { u, v } => {
+                let (t, offset) = planar(u.cross(v).normalize())?;
+                let inside = |edge: Vector3| offset.dot(edge).abs() <= edge.dot(edge) / 2.0;
+                if inside(u) && inside(v) {
+                    Some(t)
+                } else {
+                    None
+                }
+            }
+            AreaShape::Disk { normal, radius } => {
+                let (t, offset) = planar(normal)?;
+                if offset.magnitude() <= radius {
+                    Some(t)
+                } else {
+                    None
+                }
+            }
+            AreaShape::Sphere { radius } => {
+                let oc = origin - self.location;
+                let a = direction.dot(direction);
+                let b = 2.0 * oc.dot(direction);
+                let c = oc.dot(oc) - radius * radius;
+                let disc = b * b - 4.0 * a * c;
+                if disc < 0.0 {
+                    return None;
+                }
+
+                let t = (-b - disc.sqrt()) / (2.0 * a);
+                if t > 1e-6 {
+                    Some(t)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Stratified samples over the part of the light facing `point`, each
+    /// carrying its share of the light arriving at `point`.
+    pub fn sample_from(&self, point: &Point3D) -> Vec<LightSample> {
+        let mut rng = rand::thread_rng();
+        let columns = (self.samples as f64).sqrt().ceil() as usize;
+        let rows = self.samples.div_ceil(columns);
+
+        (0..self.samples)
+            .filter_map(|i| {
+                let s = ((i % columns) as f64 + rng.gen::<f64>()) / columns as f64;
+                let t = ((i / columns) as f64 + rng.gen::<f64>()) / rows as f64;
+                self.sample_point(point, s, t)
+            })
+            .map(|sample| LightSample {
+                intensity: sample.intensity / self.samples as f64,
+                ..sample
+            })
+            .collect()
+    }
+
+    /// A single sample anywhere on the light, carrying all of the light
+    /// arriving at `point`.
+    pub fn sample_one(&self, point: &Point3D) -> Option<LightSample> {
+        let mut rng = rand::thread_rng();
+        self.sample_point(point, rng.gen(), rng.gen())
+    }
+
+    fn sample_point(&self, point: &Point3D, s: f64, t: f64) -> Option<LightSample> {
+        let (position, normal, area) = match self.shape {
+            AreaShape::Rectangle { u, v } => {
+                let p = self.location + u * (s - 0.5) + v * (t - 0.5);
+                (p, u.cross(v).normalize(), self.area())
+            }
+            AreaShape::Disk { normal, radius } => {
+                // concentric sampling keeps the strata compact
+                let (a, b) = (2.0 * s - 1.0, 2.0 * t - 1.0);
+                let (r, theta) = if a.abs() > b.abs() {
+                    (a, PI / 4.0 * (b / a))
+                } else if b != 0.0 {
+                    (b, PI / 2.0 - PI / 4.0 * (a / b))
+                } else {
+                    (0.0, 0.0)
+                };
+
+                let (x, y) = orthonormal(&normal);
+                let p = self.location + (x * theta.cos() + y * theta.sin()) * (r * radius);
+                (p, normal, self.area())
+            }
+            AreaShape::Sphere { radius } => {
+                // only the hemisphere facing the point can light it
+                let axis = (point - self.location).normalize();
+                let z = s;
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * PI * t;
+                let (x, y) = orthonormal(&axis);
+                let normal = x * (r * phi.cos()) + y * (r * phi.sin()) + axis * z;
+                (self.location + normal * radius, normal, self.area() / 2.0)
+            }
+        };
+
+        let to_light = position - point;
+        let distance = to_light.magnitude();
+        if distance < 1e-6 {
+            return None;
+        }
+
+        let direction = to_light / distance;
+        let cos_light = -normal.dot(direction);
+        if cos_light <= 0.0 {
+            return None;
+        }
+
+        let intensity = self.radiance() * cos_light * area / (distance * distance);
+
+        Some(LightSample {
+            direction,
+            distance,
+            intensity,
+            tint: Color::WHITE,
+        })
+    }
+}
+
+impl From<AreaLight> for Light {
+    fn from(a: AreaLight) -> Self {
+        Light::Area(a)
+    }
+}
+
+impl Luminous for AreaLight {
+    fn color(&self) -> Color {
+        self.color
+    }
+
+    fn direction_from(&self, point: &Point3D) -> Vector3 {
+        (self.location - point).normalize()
+    }
+
+    fn distance(&self, point: &Point3D) -> f64 {
+        self.location.dist(point)
+    }
+
+    fn intensity(&self) -> f64 {
+        self.intensity
+    }
+
+    /// Estimated from the middle of the light rather than random samples, so
+    /// the same point always gets the same answer.
+    fn intensity_at(&self, point: &Point3D) -> f64 {
+        self.sample_point(point, 0.5, 0.5)
+            .map_or(0.0, |sample| sample.intensity)
+    }
+
+    fn spectrum(&self) -> Option<Spectrum> {
+        self.spectrum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_lights_match_point_lights() {
+        let far = Point3D::new(0.0, -50.0, 0.0);
+        let point_like = 1000.0 / (4.0 * PI * 50.0 * 50.0);
+
+        let sphere = AreaLight::sphere(Point3D::default(), 0.1).intensity(1000.0);
+        assert!((sphere.intensity_at(&far) / point_like - 1.0).abs() < 0.01);
+        assert_eq!(sphere.intensity_at(&far), sphere.intensity_at(&far));
+
+        // a one-sided light sends all its power forward, and the most of it
+        // straight ahead
+        let disk = AreaLight::disk(Point3D::default(), -Vector3::J, 0.1).intensity(1000.0);
+        assert!((disk.intensity_at(&far) / point_like - 4.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn only_the_front_is_lit_and_visible() {
+        let panel = AreaLight::rectangle(
+            Point3D::default(),
+            Vector3::new([2.0, 0.0, 0.0]),
+            Vector3::new([0.0, 0.0, 2.0]),
+        );
+
+        // u × v points down
+        let (below, above) = (Point3D::new(0.0, -5.0, 0.0), Point3D::new(0.0, 5.0, 0.0));
+        assert!(panel.intensity_at(&below) > 0.0);
+        assert_eq!(panel.intensity_at(&above), 0.0);
+        assert!(panel.faces(&below) && !panel.faces(&above));
+
+        let light = Light::from(panel.clone());
+        assert!(light.importance(&below) > 0.0);
+        assert_eq!(light.importance(&above), 0.0);
+
+        let up = Ray::new(Point3D::new(0.5, -5.0, 0.5), Vector3::J);
+        assert!((panel.intersect(&up).unwrap() - 5.0).abs() < 1e-9);
+        let down = Ray::new(Point3D::new(0.5, 5.0, 0.5), -Vector3::J);
+        assert!(panel.intersect(&down).is_none());
+        let beside = Ray::new(Point3D::new(1.5, -5.0, 0.5), Vector3::J);
+        assert!(beside.direction().dot(Vector3::J) > 0.0 && panel.intersect(&beside).is_none());
+    }
+}
@@ -3,6 +3,7 @@ use std::f64::INFINITY;
 use crate::{
     color::Color,
     math::{Point3D, Vector3},
+    spectrum::Spectrum,
 };
 
 use super::{Light, Luminous};
@@ -12,6 +13,7 @@ pub struct DirectionalLight {
     direction: Vector3,
     color: Color,
     intensity: f64,
+    spectrum: Option<Spectrum>,
 }
 
 impl DirectionalLight {
@@ -20,8 +22,14 @@ impl DirectionalLight {
             direction,
             color,
             intensity,
+            spectrum: None,
         }
     }
+
+    pub fn with_spectrum(mut self, spectrum: Spectrum) -> Self {
+        self.spectrum = Some(spectrum);
+        self
+    }
 }
 
 impl Default for DirectionalLight {
@@ -56,4 +64,8 @@ impl Luminous for DirectionalLight {
     fn intensity_at(&self, _point: &Point3D) -> f64 {
         self.intensity()
     }
+
+    fn spectrum(&self) -> Option<Spectrum> {
+        self.spectrum
+    }
 }
@@ -0,0 +1,299 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use crate::{
+    color::Color,
+    error::Result,
+    material::{ImageTexture, TextureCoord},
+    math::{Point2D, Point3D, Vector3},
+};
+
+use super::{cumulative, luminance, pick, Light, LightSample, Luminous};
+
+/// Light arriving from every direction, read from an equirectangular image
+/// wrapped around the scene. It's shown in place of the background color,
+/// shows up in reflections and lights diffuse surfaces, sending more shadow
+/// rays towards the brighter parts of the image.
+///
+/// Example:
+/// ```no_run
+/// use raytracer_rs::light::EnvironmentLight;
+/// use raytracer_rs::Scene;
+///
+/// let mut scene = Scene::new();
+/// let sky = EnvironmentLight::open("studio.hdr").unwrap().rotation(90.0).intensity(2.0);
+/// scene.add_light(sky);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvironmentLight {
+    map: ImageTexture,
+    rotation: f64,
+    intensity: f64,
+    samples: usize,
+    // running totals of row weights, and of texel weights within each row
+    marginal_cdf: Vec<f64>,
+    conditional_cdf: Vec<Vec<f64>>,
+    // the color averaged over the sphere, and where most light comes from
+    // before rotation
+    mean: Color,
+    dominant: Vector3,
+}
+
+impl EnvironmentLight {
+    pub fn new(map: ImageTexture) -> Self {
+        let (width, height) = (map.width(), map.height());
+
+        let mut env = EnvironmentLight {
+            map,
+            rotation: 0.0,
+            intensity: 1.0,
+            samples: 16,
+            marginal_cdf: vec![],
+            conditional_cdf: vec![],
+            mean: Color::BLACK,
+            dominant: Vector3::J,
+        };
+
+        let mut total = Color::BLACK;
+        let mut area = 0.0;
+        let mut pull = Vector3::ZERO;
+        env.conditional_cdf = (0..height)
+            .map(|y| {
+                // rows near the poles cover less of the sphere
+                let v = (y as f64 + 0.5) / height as f64;
+                let sin_theta = (PI * v).sin();
+                cumulative((0..width).map(|x| {
+                    let u = (x as f64 + 0.5) / width as f64;
+                    let color = env.map.color(&TextureCoord::new(Point2D::new(u, v), 1.0));
+                    let weight = luminance(&color) * sin_theta;
+
+                    total = total + color * sin_theta;
+                    area += sin_theta;
+                    pull = pull + env.to_direction(u, v) * weight;
+                    weight
+                }))
+            })
+            .collect::<Vec<Vec<f64>>>();
+
+        env.marginal_cdf = cumulative(env.conditional_cdf.iter().map(|row| row[row.len() - 1]));
+        env.mean = total * (1.0 / area);
+        // an evenly lit environment has no direction of its own, so it's
+        // taken to be overhead like a sky
+        if pull.magnitude() > 1e-6 * luminance(&total) {
+            env.dominant = pull.normalize();
+        }
+
+        env
+    }
+
+    /// Load an equirectangular image, preferably a Radiance `.hdr` file.
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        Ok(Self::new(ImageTexture::open(path)?))
+    }
+
+    /// Turn the environment around the vertical axis, in degrees.
+    pub fn rotation(mut self, degrees: f64) -> Self {
+        self.rotation = degrees;
+        self
+    }
+
+    pub fn intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// The number of shadow rays traced towards the environment per shading
+    /// point.
+    pub fn samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    /// The light arriving from `direction`.
+    pub fn radiance(&self, direction: &Vector3) -> Color {
+        let uv = self.to_uv(direction);
+        self.map.color(&TextureCoord::new(uv, 1.0)) * self.intensity
+    }
+
+    fn to_uv(&self, direction: &Vector3) -> Point2D {
+        let d = direction.normalize();
+        let phi = d.z().atan2(d.x()) - self.rotation.to_radians();
+        Point2D::new(
+            (0.5 + phi / (2.0 * PI)).rem_euclid(1.0),
+            d.y().clamp(-1.0, 1.0).acos() / PI,
+        )
+    }
+
+    /// The average color of the environment, weighted by the solid angle
+    /// each texel covers.
+    pub fn mean_color(&self) -> Color {
+        self.mean
+    }
+
+    /// The direction most of the light arrives from: the average of all
+    /// directions weighted by how bright they are.
+    pub fn dominant_direction(&self) -> Vector3 {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let d = self.dominant;
+        Vector3::new([d.x() * cos - d.z() * sin, d.y(), d.x() * sin + d.z() * cos])
+    }
+
+    fn to_direction(&self, u: f64, v: f64) -> Vector3 {
+        let phi = (u - 0.5) * 2.0 * PI + self.rotation.to_radians();
+        let theta = v * PI;
+        Vector3::new([
+            theta.sin() * phi.cos(),
+            theta.cos(),
+            theta.sin() * phi.sin(),
+        ])
+    }
+
+    /// Pick directions in proportion to how much light arrives from them.
+    pub fn sample_from(&self, _point: &Point3D) -> Vec<LightSample> {
+        let mut rng = rand::thread_rng();
+        (0..self.samples)
+            .filter_map(|_| self.sample_direction(&mut rng))
+            .map(|sample| LightSample {
+                intensity: sample.intensity / self.samples as f64,
+                ..sample
+            })
+            .collect()
+    }
+
+    /// A single direction, carrying all of the light arriving at `point`.
+    pub fn sample_one(&self, _point: &Point3D) -> Option<LightSample> {
+        self.sample_direction(&mut rand::thread_rng())
+    }
+
+    /// How much light the environment sends onto a surface, for choosing
+    /// between lights.
+    pub fn importance(&self) -> f64 {
+        luminance(&self.mean) * self.intensity * PI
+    }
+
+    fn sample_direction(&self, rng: &mut impl Rng) -> Option<LightSample> {
+        let total = self.marginal_cdf[self.marginal_cdf.len() - 1];
+        if total <= 0.0 {
+            return None;
+        }
+
+        let (width, height) = (self.map.width(), self.map.height());
+        let y = pick(&self.marginal_cdf, rng.gen());
+        let x = pick(&self.conditional_cdf[y], rng.gen());
+
+        let (u, v) = (
+            (x as f64 + rng.gen::<f64>()) / width as f64,
+            (y as f64 + rng.gen::<f64>()) / height as f64,
+        );
+
+        let row = &self.conditional_cdf[y];
+        let weight = row[x] - if x > 0 { row[x - 1] } else { 0.0 };
+        let sin_theta = (v * PI).sin();
+        if weight <= 0.0 || sin_theta <= 0.0 {
+            return None;
+        }
+
+        // the density in solid angle of having picked this direction
+        let pdf = weight / total * (width * height) as f64 / (2.0 * PI * PI * sin_theta);
+
+        let direction = self.to_direction(u, v);
+        Some(LightSample {
+            direction,
+            distance: f64::INFINITY,
+            intensity: 1.0 / pdf,
+            tint: self.radiance(&direction),
+        })
+    }
+}
+
+impl From<EnvironmentLight> for Light {
+    fn from(e: EnvironmentLight) -> Self {
+        Light::Environment(e)
+    }
+}
+
+impl Luminous for EnvironmentLight {
+    fn color(&self) -> Color {
+        self.mean
+    }
+
+    fn direction_from(&self, _point: &Point3D) -> Vector3 {
+        self.dominant_direction()
+    }
+
+    fn distance(&self, _point: &Point3D) -> f64 {
+        f64::INFINITY
+    }
+
+    fn intensity(&self) -> f64 {
+        self.intensity
+    }
+
+    fn intensity_at(&self, _point: &Point3D) -> f64 {
+        self.intensity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uv_mapping_round_trips() {
+        let env =
+            EnvironmentLight::new(ImageTexture::from_hdr(1, 1, &[[1.0, 1.0, 1.0]])).rotation(30.0);
+
+        for &(u, v) in [(0.1, 0.2), (0.5, 0.5), (0.9, 0.7)].iter() {
+            let uv = env.to_uv(&env.to_direction(u, v));
+            assert!((uv.x() - u).abs() < 1e-9 && (uv.y() - v).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn uniform_environments_give_their_radiance() {
+        // a white surface under a uniform sky of radiance L reflects L; the
+        // shading code divides the irradiance by π
+        let env = EnvironmentLight::new(ImageTexture::from_hdr(8, 4, &[[2.0, 2.0, 2.0]; 32]))
+            .samples(20000);
+
+        let irradiance = env
+            .sample_from(&Point3D::default())
+            .iter()
+            .map(|s| s.tint.r * s.intensity * s.direction.dot(Vector3::J).max(0.0))
+            .sum::<f64>();
+
+        assert!((irradiance / PI / 2.0 - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn bright_spots_attract_samples() {
+        let mut pixels = [[0.01, 0.01, 0.01]; 16];
+        pixels[5] = [100.0, 100.0, 100.0];
+        let env = EnvironmentLight::new(ImageTexture::from_hdr(4, 4, &pixels)).samples(200);
+        let towards = env.to_direction(1.5 / 4.0, 1.5 / 4.0);
+
+        let close = env
+            .sample_from(&Point3D::default())
+            .iter()
+            .filter(|s| s.direction.dot(towards) > 0.7)
+            .count();
+        assert!(close > 180);
+    }
+
+    #[test]
+    fn summarizes_color_and_direction() {
+        let mut pixels = [[0.0, 0.0, 0.0]; 16];
+        pixels[5] = [4.0, 2.0, 0.0];
+        let env = EnvironmentLight::new(ImageTexture::from_hdr(4, 4, &pixels)).rotation(40.0);
+        let towards = env.to_direction(1.5 / 4.0, 1.5 / 4.0);
+
+        assert!(env.direction_from(&Point3D::default()).dot(towards) > 0.99);
+        let mean = env.color();
+        assert!(mean.r > 0.0 && (mean.g / mean.r - 0.5).abs() < 1e-9 && mean.b == 0.0);
+
+        let uniform = EnvironmentLight::new(ImageTexture::from_hdr(4, 2, &[[0.5, 0.5, 0.5]; 8]));
+        assert!((uniform.color().g - 0.5).abs() < 1e-9);
+        assert_eq!(uniform.direction_from(&Point3D::default()), Vector3::J);
+    }
+}
@@ -0,0 +1,297 @@
+use std::{f64::consts::PI, path::Path};
+
+use crate::{
+    error::{RTError, Result},
+    math::Vector3,
+};
+
+use super::orthonormal;
+
+/// The measured light distribution of a real fixture, read from an IES
+/// LM-63 photometric file. Only type C photometry, by far the most common,
+/// is supported.
+///
+/// Vertical angles start at 0° pointing straight down the fixture's axis,
+/// horizontal angles go around that axis.
+///
+/// Example:
+/// ```no_run
+/// use raytracer_rs::light::{IesProfile, PointLight};
+/// use raytracer_rs::math::Point3D;
+///
+/// let downlight = PointLight::new(Point3D::new(0.0, 3.0, 0.0))
+///     .profile(IesProfile::open("downlight.ies").unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct IesProfile {
+    vertical: Vec<f64>,
+    horizontal: Vec<f64>,
+    // one column of candela values per horizontal angle
+    candela: Vec<Vec<f64>>,
+    // the candela value averaged over the sphere
+    mean: f64,
+}
+
+impl IesProfile {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        // keywords come first, and the photometric data follows the TILT line
+        let mut lines = text.lines();
+        let tilt = lines
+            .by_ref()
+            .map(str::trim)
+            .find(|line| line.starts_with("TILT="))
+            .ok_or_else(|| invalid("missing TILT line"))?;
+
+        let rest = lines.collect::<Vec<&str>>().join(" ");
+        let mut numbers = rest
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|token| !token.is_empty())
+            .map(str::parse::<f64>);
+        let mut next = || -> Result<f64> {
+            Ok(numbers
+                .next()
+                .ok_or_else(|| invalid("unexpected end of data"))??)
+        };
+
+        if tilt == "TILT=INCLUDE" {
+            // lamp to luminaire geometry, then pairs of angles and factors
+            next()?;
+            let count = next()? as usize;
+            for _ in 0..2 * count {
+                next()?;
+            }
+        }
+
+        let _lamps = next()?;
+        let _lumens = next()?;
+        let multiplier = next()?;
+        let vertical_count = next()? as usize;
+        let horizontal_count = next()? as usize;
+        let photometric_type = next()?;
+        // units, width, length and height of the opening
+        for _ in 0..4 {
+            next()?;
+        }
+        let ballast = next()?;
+        let _future_use = next()?;
+        let _watts = next()?;
+
+        if photometric_type != 1.0 {
+            return Err(invalid("only type C photometry is supported"));
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err(invalid("no angles"));
+        }
+
+        let vertical = (0..vertical_count)
+            .map(|_| next())
+            .collect::<Result<Vec<f64>>>()?;
+        let horizontal = (0..horizontal_count)
+            .map(|_| next())
+            .collect::<Result<Vec<f64>>>()?;
+        let candela = (0..horizontal_count)
+            .map(|_| {
+                (0..vertical_count)
+                    .map(|_| Ok(next()? * multiplier * ballast))
+                    .collect::<Result<Vec<f64>>>()
+            })
+            .collect::<Result<Vec<Vec<f64>>>>()?;
+
+        let sorted = |angles: &[f64]| angles.windows(2).all(|w| w[0] < w[1]);
+        if !sorted(&vertical) || !sorted(&horizontal) {
+            return Err(invalid("angles must be increasing"));
+        }
+
+        let mut profile = IesProfile {
+            vertical,
+            horizontal,
+            candela,
+            mean: 1.0,
+        };
+        profile.mean = profile.spherical_mean();
+        if profile.mean <= 0.0 {
+            return Err(invalid("the fixture emits no light"));
+        }
+
+        Ok(profile)
+    }
+
+    /// The measured intensity in candela at the given angles, in degrees.
+    pub fn candela(&self, vertical: f64, horizontal: f64) -> f64 {
+        let (first, last) = (self.vertical[0], self.vertical[self.vertical.len() - 1]);
+        if vertical < first || vertical > last {
+            return 0.0;
+        }
+
+        let h = self.fold(horizontal.rem_euclid(360.0));
+        let column = |i: usize| interpolate(&self.vertical, &self.candela[i], vertical);
+
+        match segment(&self.horizontal, h) {
+            Some((i, t)) => column(i) * (1.0 - t) + column(i + 1) * t,
+            None => column(0),
+        }
+    }
+
+    /// How bright the fixture is towards `direction` relative to a uniform
+    /// light of the same total power. `down` is the fixture's axis and
+    /// `reference` is roughly the direction of 0° horizontal. A zero
+    /// `direction` has no angle to look up, so it's treated as uniform.
+    pub fn scale(&self, direction: &Vector3, down: &Vector3, reference: &Vector3) -> f64 {
+        if direction.magnitude() < 1e-9 {
+            return 1.0;
+        }
+
+        let d = direction.normalize();
+        let down = down.normalize();
+
+        // keep the reference axis perpendicular to the fixture's axis
+        let across = down.cross(*reference);
+        let (x, y) = if across.magnitude() < 1e-9 {
+            orthonormal(&down)
+        } else {
+            let y = across.normalize();
+            (y.cross(down), y)
+        };
+
+        let vertical = d.dot(down).clamp(-1.0, 1.0).acos().to_degrees();
+        let horizontal = d.dot(y).atan2(d.dot(x)).to_degrees();
+        self.candela(vertical, horizontal) / self.mean
+    }
+
+    /// Map a horizontal angle onto the range the file covers, following its
+    /// symmetry.
+    fn fold(&self, h: f64) -> f64 {
+        let last = self.horizontal[self.horizontal.len() - 1];
+        if last <= 0.0 {
+            // rotationally symmetric
+            0.0
+        } else if last <= 90.0 {
+            let h = if h > 180.0 { 360.0 - h } else { h };
+            if h > 90.0 {
+                180.0 - h
+            } else {
+                h
+            }
+        } else if last <= 180.0 && h > 180.0 {
+            360.0 - h
+        } else {
+            h
+        }
+    }
+
+    fn spherical_mean(&self) -> f64 {
+        const STEPS: usize = 90;
+
+        let mut total = 0.0;
+        let mut weight = 0.0;
+        for i in 0..STEPS {
+            let theta = (i as f64 + 0.5) / STEPS as f64 * PI;
+            for j in 0..2 * STEPS {
+                let phi = (j as f64 + 0.5) / (2 * STEPS) as f64 * 360.0;
+                total += self.candela(theta.to_degrees(), phi) * theta.sin();
+                weight += theta.sin();
+            }
+        }
+
+        total / weight
+    }
+}
+
+/// The index of the segment of `xs` containing `x`, and how far along it `x`
+/// is. `None` if there's only one value.
+fn segment(xs: &[f64], x: f64) -> Option<(usize, f64)> {
+    if xs.len() < 2 {
+        return None;
+    }
+
+    let i = xs.partition_point(|&a| a <= x).clamp(1, xs.len() - 1) - 1;
+    let t = ((x - xs[i]) / (xs[i + 1] - xs[i])).clamp(0.0, 1.0);
+    Some((i, t))
+}
+
+fn interpolate(xs: &[f64], ys: &[f64], x: f64) -> f64 {
+    match segment(xs, x) {
+        Some((i, t)) => ys[i] * (1.0 - t) + ys[i + 1] * t,
+        None => ys[0],
+    }
+}
+
+fn invalid(msg: &str) -> RTError {
+    RTError::InvalidIes(msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOWNLIGHT: &str = "IESNA:LM-63-2002
+[TEST] a rotationally symmetric downlight
+[MANUFAC] nobody
+TILT=NONE
+1 1000 1.0 5 1 1 2 0.1 0.1 0.0
+1.0 1.0 20
+0 22.5 45 67.5 90
+0
+1000, 900, 500,
+100 0
+";
+
+    #[test]
+    fn parses_and_interpolates() {
+        let profile = IesProfile::parse(DOWNLIGHT).unwrap();
+
+        assert_eq!(profile.candela(0.0, 0.0), 1000.0);
+        assert_eq!(profile.candela(11.25, 123.0), 950.0);
+        assert_eq!(profile.candela(45.0, 300.0), 500.0);
+        // nothing is measured above the horizon
+        assert_eq!(profile.candela(120.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn scale_preserves_total_power() {
+        let profile = IesProfile::parse(DOWNLIGHT).unwrap();
+        let down = -Vector3::J;
+
+        let steps = 200;
+        let mean = (0..steps)
+            .map(|i| {
+                let theta = (i as f64 + 0.5) / steps as f64 * PI;
+                let d = Vector3::new([theta.sin(), theta.cos(), 0.0]);
+                profile.scale(&d, &down, &Vector3::I) * theta.sin()
+            })
+            .sum::<f64>()
+            / (0..steps)
+                .map(|i| ((i as f64 + 0.5) / steps as f64 * PI).sin())
+                .sum::<f64>();
+        assert!((mean - 1.0).abs() < 0.01);
+
+        assert!(profile.scale(&down, &down, &Vector3::I) > 1.0);
+        assert_eq!(profile.scale(&Vector3::J, &down, &Vector3::I), 0.0);
+        assert_eq!(profile.scale(&Vector3::ZERO, &down, &Vector3::I), 1.0);
+    }
+
+    #[test]
+    fn quadrant_symmetry_mirrors_angles() {
+        let text = "TILT=NONE
+1 -1 2.0 2 3 1 1 0 0 0
+1 1 0
+0 90
+0 45 90
+10 0
+20 0
+30 0
+";
+        let profile = IesProfile::parse(text).unwrap();
+        assert_eq!(profile.candela(0.0, 0.0), 20.0);
+        assert_eq!(profile.candela(0.0, 135.0), 40.0);
+        assert_eq!(profile.candela(0.0, 270.0), 60.0);
+        assert_eq!(profile.candela(0.0, 315.0), 40.0);
+
+        assert!(IesProfile::parse("TILT=NONE\n1 1000").is_err());
+        assert!(IesProfile::parse("no tilt line").is_err());
+    }
+}
@@ -1,13 +1,38 @@
+pub use crate::light::ambient::{AmbientLight, HemisphereLight, Occlusion};
+pub use crate::light::area::{AreaLight, AreaShape};
 pub use crate::light::directional::DirectionalLight;
-pub use crate::light::point::PointLight;
+pub use crate::light::environment::EnvironmentLight;
+pub use crate::light::ies::IesProfile;
+pub use crate::light::point::{Falloff, LightUnit, PointLight};
+pub use crate::light::sky::SkyLight;
+pub use crate::light::spot::SpotLight;
 
 use crate::{
     color::Color,
     math::{Point3D, Vector3},
+    ray::Ray,
+    spectrum::Spectrum,
 };
 
+mod ambient;
+mod area;
 mod directional;
+mod environment;
+mod ies;
 mod point;
+mod sky;
+mod spot;
+
+/// The light arriving at a point from one direction: all of a point light,
+/// or part of an area or environment light. `tint` scales the color of the
+/// light for lights whose color varies by direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightSample {
+    pub direction: Vector3,
+    pub distance: f64,
+    pub intensity: f64,
+    pub tint: Color,
+}
 
 pub trait Luminous: Into<Light> {
     fn color(&self) -> Color;
@@ -15,12 +40,22 @@ pub trait Luminous: Into<Light> {
     fn distance(&self, point: &Point3D) -> f64;
     fn intensity(&self) -> f64;
     fn intensity_at(&self, point: &Point3D) -> f64;
+
+    fn spectrum(&self) -> Option<Spectrum> {
+        None
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Light {
     Directional(DirectionalLight),
     Point(PointLight),
+    Spot(SpotLight),
+    Area(AreaLight),
+    Environment(EnvironmentLight),
+    Sky(SkyLight),
+    Ambient(AmbientLight),
+    Hemisphere(HemisphereLight),
 }
 
 impl Light {
@@ -28,6 +63,38 @@ impl Light {
         match self {
             Light::Directional(light) => light.color(),
             Light::Point(light) => light.color(),
+            Light::Spot(light) => light.color(),
+            Light::Area(light) => light.color(),
+            Light::Environment(light) => light.color(),
+            Light::Sky(light) => light.color(),
+            Light::Ambient(light) => light.color(),
+            Light::Hemisphere(light) => light.color(),
+        }
+    }
+
+    pub fn spectrum(&self) -> Option<Spectrum> {
+        match self {
+            Light::Directional(light) => light.spectrum(),
+            Light::Point(light) => light.spectrum(),
+            Light::Spot(light) => light.spectrum(),
+            Light::Area(light) => light.spectrum(),
+            Light::Environment(light) => light.spectrum(),
+            Light::Sky(light) => light.spectrum(),
+            Light::Ambient(light) => light.spectrum(),
+            Light::Hemisphere(light) => light.spectrum(),
+        }
+    }
+
+    /// The color of this light as seen by a ray of the given wavelength (see
+    /// [Color::at_wavelength](crate::color::Color::at_wavelength)), including
+    /// its emission spectrum.
+    pub fn color_at_wavelength(&self, wavelength: Option<f64>) -> Color {
+        match (self.spectrum(), wavelength) {
+            (Some(spectrum), Some(wavelength)) => {
+                self.color().at_wavelength(Some(wavelength)) * spectrum.value(wavelength)
+            }
+            (Some(spectrum), None) => self.color() * spectrum.color(),
+            (None, _) => self.color().at_wavelength(wavelength),
         }
     }
 
@@ -35,6 +102,12 @@ impl Light {
         match self {
             Light::Directional(light) => light.direction_from(point),
             Light::Point(light) => light.direction_from(point),
+            Light::Spot(light) => light.direction_from(point),
+            Light::Area(light) => light.direction_from(point),
+            Light::Environment(light) => light.direction_from(point),
+            Light::Sky(light) => light.direction_from(point),
+            Light::Ambient(light) => light.direction_from(point),
+            Light::Hemisphere(light) => light.direction_from(point),
         }
     }
 
@@ -42,6 +115,12 @@ impl Light {
         match self {
             Light::Directional(light) => light.distance(point),
             Light::Point(light) => light.distance(point),
+            Light::Spot(light) => light.distance(point),
+            Light::Area(light) => light.distance(point),
+            Light::Environment(light) => light.distance(point),
+            Light::Sky(light) => light.distance(point),
+            Light::Ambient(light) => light.distance(point),
+            Light::Hemisphere(light) => light.distance(point),
         }
     }
 
@@ -49,6 +128,133 @@ impl Light {
         match self {
             Light::Directional(light) => light.intensity_at(point),
             Light::Point(light) => light.intensity_at(point),
+            Light::Spot(light) => light.intensity_at(point),
+            Light::Area(light) => light.intensity_at(point),
+            Light::Environment(light) => light.intensity_at(point),
+            Light::Sky(light) => light.intensity_at(point),
+            Light::Ambient(light) => light.intensity_at(point),
+            Light::Hemisphere(light) => light.intensity_at(point),
+        }
+    }
+
+    /// A cheap estimate of how much light reaches `point`, ignoring shadows,
+    /// for choosing between many lights.
+    pub fn importance(&self, point: &Point3D) -> f64 {
+        match self {
+            Light::Area(light) if !light.faces(point) => 0.0,
+            Light::Area(light) => {
+                let r2 = light.distance(point).powi(2).max(1e-6);
+                light.intensity() / (4.0 * std::f64::consts::PI * r2)
+            }
+            Light::Environment(light) => light.importance(),
+            Light::Sky(light) => light.importance(),
+            // applied separately, without shadow rays
+            Light::Ambient(_) | Light::Hemisphere(_) => 0.0,
+            _ => self.intensity_at(point),
+        }
+    }
+
+    /// Where light arrives at `point` from, with one sample for point-like
+    /// lights and several spread over the surface of area lights.
+    pub fn sample_from(&self, point: &Point3D) -> Vec<LightSample> {
+        match self {
+            Light::Area(light) => light.sample_from(point),
+            Light::Environment(light) => light.sample_from(point),
+            Light::Sky(light) => light.sample_from(point),
+            Light::Ambient(_) | Light::Hemisphere(_) => vec![],
+            _ => vec![LightSample {
+                direction: self.direction_from(point),
+                distance: self.distance(point),
+                intensity: self.intensity_at(point),
+                tint: Color::WHITE,
+            }],
+        }
+    }
+
+    /// A single sample of the light arriving at `point`, carrying all of it,
+    /// for when only one shadow ray can be spared for this light.
+    pub fn sample_one(&self, point: &Point3D) -> Option<LightSample> {
+        match self {
+            Light::Area(light) => light.sample_one(point),
+            Light::Environment(light) => light.sample_one(point),
+            Light::Sky(light) => light.sample_one(point),
+            Light::Ambient(_) | Light::Hemisphere(_) => None,
+            _ => Some(LightSample {
+                direction: self.direction_from(point),
+                distance: self.distance(point),
+                intensity: self.intensity_at(point),
+                tint: Color::WHITE,
+            }),
         }
     }
+
+    /// The distance along `ray` to the light, for lights with a visible
+    /// surface.
+    pub fn intersect(&self, ray: &Ray) -> Option<f64> {
+        match self {
+            Light::Area(light) => light.intersect(ray),
+            _ => None,
+        }
+    }
+
+    /// The light arriving at a surface facing `normal` from lights that cast
+    /// no shadows, and how to darken it with ambient occlusion.
+    pub fn ambient(&self, normal: &Vector3) -> Option<(Color, Option<Occlusion>)> {
+        match self {
+            Light::Ambient(light) => Some((light.radiance(), light.occlusion())),
+            Light::Hemisphere(light) => Some((light.radiance(normal), light.occlusion())),
+            _ => None,
+        }
+    }
+
+    /// The light seen by a ray that hits nothing, for lights surrounding the
+    /// scene.
+    pub fn background(&self, direction: &Vector3) -> Option<Color> {
+        match self {
+            Light::Environment(light) => Some(light.radiance(direction)),
+            Light::Sky(light) => Some(light.radiance(direction)),
+            _ => None,
+        }
+    }
+
+    /// The color of the visible surface of the light.
+    pub fn radiance(&self, wavelength: Option<f64>) -> Color {
+        match self {
+            Light::Area(light) => self.color_at_wavelength(wavelength) * light.radiance(),
+            _ => Color::BLACK,
+        }
+    }
+}
+
+/// Running totals of `weights`, ignoring negative ones.
+pub(crate) fn cumulative(weights: impl Iterator<Item = f64>) -> Vec<f64> {
+    let mut total = 0.0;
+    weights
+        .map(|w| {
+            total += w.max(0.0);
+            total
+        })
+        .collect()
+}
+
+/// The index whose share of `cdf` contains `xi` (in [0, 1)).
+pub(crate) fn pick(cdf: &[f64], xi: f64) -> usize {
+    let target = xi * cdf[cdf.len() - 1];
+    cdf.partition_point(|&c| c <= target).min(cdf.len() - 1)
+}
+
+/// How bright `c` looks, weighting each channel by the eye's sensitivity.
+pub(crate) fn luminance(c: &Color) -> f64 {
+    0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b
+}
+
+/// Two unit vectors perpendicular to `n` and each other.
+fn orthonormal(n: &Vector3) -> (Vector3, Vector3) {
+    let helper = if n.x().abs() > 0.9 {
+        Vector3::J
+    } else {
+        Vector3::I
+    };
+    let x = n.cross(helper).normalize();
+    (x, n.cross(x))
 }
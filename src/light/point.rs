@@ -3,15 +3,72 @@ use std::f64::consts::PI;
 use crate::{
     color::Color,
     math::{Point3D, Vector3},
+    spectrum::Spectrum,
 };
 
-use super::{Light, Luminous};
+use super::{IesProfile, Light, Luminous};
+
+/// Lumens per watt of light at 555nm, where the eye is most sensitive.
+const LUMINOUS_EFFICACY: f64 = 683.0;
+
+/// Points closer than this are lit as if they were this far away, as though
+/// the light were a small bulb rather than a point. Without it, surfaces
+/// touching the light would get an unbounded intensity.
+const MIN_RADIUS: f64 = 0.01;
+
+/// How the light from a [PointLight] dims with distance.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Falloff {
+    /// The same brightness at any distance.
+    None,
+    Linear,
+    /// Physically correct falloff.
+    #[default]
+    InverseSquare,
+    /// Inverse square falloff, faded out smoothly to nothing at `radius`, so
+    /// the light can be skipped beyond it.
+    Smooth {
+        radius: f64,
+    },
+}
+
+impl Falloff {
+    /// The attenuation at the given squared distance.
+    pub fn attenuation(&self, r2: f64) -> f64 {
+        match self {
+            Falloff::None => 1.0,
+            Falloff::Linear => 1.0 / r2.sqrt(),
+            Falloff::InverseSquare => 1.0 / r2,
+            Falloff::Smooth { radius } => {
+                let ratio = r2 / (radius * radius);
+                let window = (1.0 - ratio * ratio).max(0.0);
+                window * window / r2
+            }
+        }
+    }
+}
+
+/// The unit of a [PointLight]'s intensity, which is its total power.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LightUnit {
+    #[default]
+    Watts,
+    /// Converted to watts at the peak luminous efficacy of 683 lm/W.
+    Lumens,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct PointLight {
     location: Point3D,
     color: Color,
     intensity: f64,
+    falloff: Falloff,
+    unit: LightUnit,
+    profile: Option<IesProfile>,
+    // the fixture's axis and the direction of its 0° horizontal angle
+    down: Vector3,
+    reference: Vector3,
+    spectrum: Option<Spectrum>,
 }
 
 impl PointLight {
@@ -20,6 +77,12 @@ impl PointLight {
             location,
             color: Color::WHITE,
             intensity: 3000.0,
+            falloff: Falloff::InverseSquare,
+            unit: LightUnit::Watts,
+            profile: None,
+            down: -Vector3::J,
+            reference: Vector3::I,
+            spectrum: None,
         }
     }
 
@@ -32,6 +95,47 @@ impl PointLight {
         self.intensity = intensity;
         self
     }
+
+    pub fn falloff(mut self, falloff: Falloff) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    pub fn unit(mut self, unit: LightUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Shape the light by a measured photometric profile, with the fixture
+    /// pointing down and its 0° horizontal angle towards +x unless turned with
+    /// [orientation](Self::orientation). The total power stays the same.
+    pub fn profile(mut self, profile: IesProfile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    /// Point the profile's axis along `down`, with its 0° horizontal angle
+    /// towards `reference`. A zero `down` is ignored.
+    pub fn orientation(mut self, down: Vector3, reference: Vector3) -> Self {
+        if down.magnitude() > 1e-9 {
+            self.down = down.normalize();
+        }
+        self.reference = reference;
+        self
+    }
+
+    pub fn spectrum(mut self, spectrum: Spectrum) -> Self {
+        self.spectrum = Some(spectrum);
+        self
+    }
+
+    /// The intensity in watts.
+    pub fn power(&self) -> f64 {
+        match self.unit {
+            LightUnit::Watts => self.intensity,
+            LightUnit::Lumens => self.intensity / LUMINOUS_EFFICACY,
+        }
+    }
 }
 
 impl From<PointLight> for Light {
@@ -64,13 +168,64 @@ impl Luminous for PointLight {
     }
 
     fn intensity_at(&self, point: &Point3D) -> f64 {
-        let r2 = (self.location - point).norm();
+        let r2 = (self.location - point).norm().max(MIN_RADIUS * MIN_RADIUS);
+        let shape = self.profile.as_ref().map_or(1.0, |profile| {
+            profile.scale(&(point - self.location), &self.down, &self.reference)
+        });
 
-        // TODO: figure out something better to do here - MCL - 2021-07-18
-        if r2 < 1e-6_f64 {
-            return self.intensity();
-        }
+        shape * self.power() / (4.0 * PI) * self.falloff.attenuation(r2)
+    }
+
+    fn spectrum(&self) -> Option<Spectrum> {
+        self.spectrum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falloff_models() {
+        let light = PointLight::new(Point3D::default()).intensity(4.0 * PI);
+        let at = |light: &PointLight, d: f64| light.intensity_at(&Point3D::new(d, 0.0, 0.0));
+
+        assert!((at(&light, 2.0) - 0.25).abs() < 1e-12);
+        assert!((at(&light.clone().falloff(Falloff::Linear), 2.0) - 0.5).abs() < 1e-12);
+        assert_eq!(at(&light.clone().falloff(Falloff::None), 2.0), 1.0);
+
+        let smooth = light.clone().falloff(Falloff::Smooth { radius: 4.0 });
+        assert!(at(&smooth, 0.5) / at(&light, 0.5) > 0.99);
+        assert!(at(&smooth, 3.0) < at(&light, 3.0));
+        assert_eq!(at(&smooth, 4.5), 0.0);
+
+        // right next to the light it's as bright as at the bulb's surface
+        assert_eq!(at(&light, 0.0), at(&light, MIN_RADIUS));
+        assert!(at(&light, 0.0) <= 1.0 / (MIN_RADIUS * MIN_RADIUS) + 1e-9);
+
+        let profile =
+            IesProfile::parse("TILT=NONE\n1 -1 1 2 1 1 1 0 0 0\n1 1 0\n0 90\n0\n100 0\n").unwrap();
+        assert!(at(&light.clone().profile(profile), 0.0).is_finite());
+    }
+
+    #[test]
+    fn lumens_convert_to_watts() {
+        let watts = PointLight::new(Point3D::default()).intensity(1.0);
+        let lumens = watts.clone().intensity(683.0).unit(LightUnit::Lumens);
+        assert_eq!(watts.power(), lumens.power());
+    }
+
+    #[test]
+    fn profiles_follow_the_orientation() {
+        // all of the light within 90° of the axis
+        let profile =
+            IesProfile::parse("TILT=NONE\n1 -1 1 2 1 1 1 0 0 0\n1 1 0\n0 90\n0\n100 0\n").unwrap();
+        let light = PointLight::new(Point3D::default()).profile(profile);
+        let sideways = light.clone().orientation(Vector3::I, Vector3::J);
 
-        self.intensity() / (4.0 * PI * r2)
+        let below = Point3D::new(0.0, -1.0, 0.0);
+        let beside = Point3D::new(1.0, 0.0, 0.0);
+        assert!(light.intensity_at(&below) > light.intensity_at(&beside));
+        assert!(sideways.intensity_at(&beside) > sideways.intensity_at(&below));
     }
 }
@@ -0,0 +1,399 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use crate::{
+    color::Color,
+    material::ImageTexture,
+    math::{Point3D, Vector3},
+    spectrum::xyz_to_rgb,
+};
+
+use super::{luminance, orthonormal, EnvironmentLight, Light, LightSample, Luminous};
+
+/// Resolution of the image the sky is baked into for importance sampling.
+const BAKE_WIDTH: usize = 64;
+const BAKE_HEIGHT: usize = 32;
+
+/// A clear daylight sky following the Preetham model, with a sun disk of
+/// finite size for soft sun shadows. The sky lights the scene like an
+/// [EnvironmentLight] and shows up as the background.
+///
+/// The sun is placed by its elevation above the horizon and its azimuth,
+/// both in degrees; an azimuth of 0 puts it towards +z and 90 towards +x.
+/// Turbidity goes from 2 (very clear) to about 10 (hazy).
+///
+/// Example:
+/// ```
+/// use raytracer_rs::light::SkyLight;
+/// use raytracer_rs::Scene;
+///
+/// let mut scene = Scene::new();
+/// scene.add_light(SkyLight::new(35.0, 120.0).turbidity(3.0).sun_size(1.0));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkyLight {
+    elevation: f64,
+    azimuth: f64,
+    turbidity: f64,
+    sky_intensity: f64,
+    sun_intensity: f64,
+    sun_size: f64,
+    samples: usize,
+    // the sky without the sun, for sampling the light it sends
+    dome: EnvironmentLight,
+}
+
+impl SkyLight {
+    pub fn new(elevation: f64, azimuth: f64) -> Self {
+        SkyLight {
+            elevation,
+            azimuth,
+            turbidity: 2.5,
+            sky_intensity: 0.05,
+            sun_intensity: 3.0,
+            sun_size: 0.53,
+            samples: 16,
+            dome: EnvironmentLight::new(ImageTexture::from_hdr(1, 1, &[[0.0; 3]])),
+        }
+        .bake()
+    }
+
+    pub fn turbidity(mut self, turbidity: f64) -> Self {
+        self.turbidity = turbidity.clamp(1.7, 10.0);
+        self.bake()
+    }
+
+    /// Scales the brightness of the sky. The sky's luminance is in kcd/m², so
+    /// this is small.
+    pub fn intensity(mut self, intensity: f64) -> Self {
+        self.sky_intensity = intensity;
+        self.bake()
+    }
+
+    /// The irradiance of the sun at the top of the atmosphere, before it's
+    /// dimmed and reddened on the way through.
+    pub fn sun_intensity(mut self, intensity: f64) -> Self {
+        self.sun_intensity = intensity;
+        self
+    }
+
+    /// The angular diameter of the sun disk, in degrees. Larger suns cast
+    /// softer shadows.
+    pub fn sun_size(mut self, degrees: f64) -> Self {
+        self.sun_size = degrees.max(0.0);
+        self
+    }
+
+    /// The number of shadow rays traced towards the sky, and towards the sun,
+    /// per shading point.
+    pub fn samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(1);
+        self.dome = self.dome.samples(self.samples);
+        self
+    }
+
+    /// The direction towards the center of the sun.
+    pub fn sun_direction(&self) -> Vector3 {
+        let (elevation, azimuth) = (self.elevation.to_radians(), self.azimuth.to_radians());
+        Vector3::new([
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            elevation.cos() * azimuth.cos(),
+        ])
+    }
+
+    /// The color of sunlight after passing through the atmosphere.
+    pub fn sun_color(&self) -> Color {
+        let theta = (90.0 - self.elevation).to_radians();
+        if theta >= PI / 2.0 {
+            return Color::BLACK;
+        }
+
+        // relative optical mass, and the Ångström turbidity coefficient
+        let mass = 1.0 / (theta.cos() + 0.15 * (93.885 - theta.to_degrees()).powf(-1.253));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+
+        // Rayleigh and aerosol extinction at roughly the sRGB primaries, in μm
+        let transmittance = |lambda: f64| {
+            let rayleigh = (-0.008735 * lambda.powf(-4.08) * mass).exp();
+            let aerosol = (-beta * lambda.powf(-1.3) * mass).exp();
+            rayleigh * aerosol
+        };
+
+        Color::new(
+            transmittance(0.68),
+            transmittance(0.55),
+            transmittance(0.44),
+        )
+    }
+
+    /// The light arriving from `direction`, including the sun disk.
+    pub fn radiance(&self, direction: &Vector3) -> Color {
+        let d = direction.normalize();
+        let sky = self.sky(&d);
+
+        if d.dot(self.sun_direction()) >= self.sun_cos() {
+            sky + self.sun_color() * (self.sun_intensity / self.sun_solid_angle())
+        } else {
+            sky
+        }
+    }
+
+    fn sun_cos(&self) -> f64 {
+        (self.sun_size / 2.0).to_radians().cos()
+    }
+
+    fn sun_solid_angle(&self) -> f64 {
+        (2.0 * PI * (1.0 - self.sun_cos())).max(1e-12)
+    }
+
+    /// The Preetham sky, without the sun disk.
+    fn sky(&self, direction: &Vector3) -> Color {
+        let t = self.turbidity;
+        let theta_s = (90.0 - self.elevation.max(0.0)).to_radians().min(PI / 2.0);
+
+        // the horizon carries on below it
+        let cos_theta = direction.y().max(0.0);
+        let sun = self.sun_direction();
+        let sun = Vector3::new([sun.x(), theta_s.cos(), sun.z()]).normalize();
+        let gamma = direction.dot(sun).clamp(-1.0, 1.0).acos();
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_y = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let zenith_x = zenith_chromaticity(
+            t,
+            theta_s,
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        );
+        let zenith_yc = zenith_chromaticity(
+            t,
+            theta_s,
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        );
+
+        let luminance = [
+            0.1787 * t - 1.4630,
+            -0.3554 * t + 0.4275,
+            -0.0227 * t + 5.3251,
+            0.1206 * t - 2.5771,
+            -0.0670 * t + 0.3703,
+        ];
+        let x_coeffs = [
+            -0.0193 * t - 0.2592,
+            -0.0665 * t + 0.0008,
+            -0.0004 * t + 0.2125,
+            -0.0641 * t - 0.8989,
+            -0.0033 * t + 0.0452,
+        ];
+        let y_coeffs = [
+            -0.0167 * t - 0.2608,
+            -0.0950 * t + 0.0092,
+            -0.0079 * t + 0.2102,
+            -0.0441 * t - 1.6537,
+            -0.0109 * t + 0.0529,
+        ];
+
+        // each quantity is its zenith value scaled by the Perez distribution
+        let relative = |c: &[f64; 5]| perez(c, cos_theta, gamma) / perez(c, 1.0, theta_s);
+
+        let luma = zenith_y * relative(&luminance) * self.sky_intensity;
+        let x = zenith_x * relative(&x_coeffs);
+        let y = zenith_yc * relative(&y_coeffs);
+        if y <= 0.0 || luma <= 0.0 {
+            return Color::BLACK;
+        }
+
+        let c = xyz_to_rgb(x / y * luma, luma, (1.0 - x - y) / y * luma);
+        Color::new(c.r.max(0.0), c.g.max(0.0), c.b.max(0.0))
+    }
+
+    /// Bake the sky into the equirectangular layout of [EnvironmentLight],
+    /// so shadow rays go where the sky is brightest.
+    fn bake(mut self) -> Self {
+        let pixels = (0..BAKE_HEIGHT)
+            .flat_map(|y| (0..BAKE_WIDTH).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let phi = ((x as f64 + 0.5) / BAKE_WIDTH as f64 - 0.5) * 2.0 * PI;
+                let theta = (y as f64 + 0.5) / BAKE_HEIGHT as f64 * PI;
+                let direction = Vector3::new([
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                ]);
+                let c = self.sky(&direction);
+                [c.r as f32, c.g as f32, c.b as f32]
+            })
+            .collect::<Vec<[f32; 3]>>();
+
+        self.dome = EnvironmentLight::new(ImageTexture::from_hdr(BAKE_WIDTH, BAKE_HEIGHT, &pixels))
+            .samples(self.samples);
+        self
+    }
+
+    /// Samples of the sky, followed by samples spread over the sun disk.
+    pub fn sample_from(&self, point: &Point3D) -> Vec<LightSample> {
+        let mut samples = self.dome.sample_from(point);
+        if self.sun_color() == Color::BLACK {
+            return samples;
+        }
+
+        let mut rng = rand::thread_rng();
+        samples.extend((0..self.samples).map(|_| {
+            let sample = self.sun_sample(&mut rng);
+            LightSample {
+                intensity: sample.intensity / self.samples as f64,
+                ..sample
+            }
+        }));
+
+        samples
+    }
+
+    /// A single sample of either the sky or the sun, picked evenly, carrying
+    /// all of the light arriving at `point`.
+    pub fn sample_one(&self, point: &Point3D) -> Option<LightSample> {
+        if self.sun_color() == Color::BLACK || self.sun_intensity <= 0.0 {
+            return self.dome.sample_one(point);
+        }
+
+        let mut rng = rand::thread_rng();
+        let sample = if rng.gen::<bool>() {
+            self.dome.sample_one(point)?
+        } else {
+            self.sun_sample(&mut rng)
+        };
+        Some(LightSample {
+            intensity: sample.intensity * 2.0,
+            ..sample
+        })
+    }
+
+    /// How much light the sky and sun send onto a surface, for choosing
+    /// between lights.
+    pub fn importance(&self) -> f64 {
+        self.dome.importance() + luminance(&self.sun_color()) * self.sun_intensity
+    }
+
+    /// A direction on the sun disk carrying all of the sun's light.
+    fn sun_sample(&self, rng: &mut impl Rng) -> LightSample {
+        let axis = self.sun_direction();
+        let (u, v) = orthonormal(&axis);
+        let cos_max = self.sun_cos();
+
+        let cos_theta = 1.0 - rng.gen::<f64>() * (1.0 - cos_max);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.gen::<f64>();
+
+        LightSample {
+            direction: (u * (sin_theta * phi.cos())
+                + v * (sin_theta * phi.sin())
+                + axis * cos_theta)
+                .normalize(),
+            distance: f64::INFINITY,
+            intensity: self.sun_intensity,
+            tint: self.sun_color(),
+        }
+    }
+}
+
+/// The Perez luminance distribution.
+fn perez(c: &[f64; 5], cos_theta: f64, gamma: f64) -> f64 {
+    let cos_gamma = gamma.cos();
+    (1.0 + c[0] * (c[1] / cos_theta.max(0.01)).exp())
+        * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * cos_gamma * cos_gamma)
+}
+
+/// One of the zenith chromaticity polynomials, in turbidity and sun angle.
+fn zenith_chromaticity(t: f64, theta: f64, t2: [f64; 4], t1: [f64; 4], t0: [f64; 4]) -> f64 {
+    let cubic = |c: [f64; 4]| ((c[0] * theta + c[1]) * theta + c[2]) * theta + c[3];
+    t * t * cubic(t2) + t * cubic(t1) + cubic(t0)
+}
+
+impl From<SkyLight> for Light {
+    fn from(s: SkyLight) -> Self {
+        Light::Sky(s)
+    }
+}
+
+impl Luminous for SkyLight {
+    fn color(&self) -> Color {
+        self.sun_color()
+    }
+
+    fn direction_from(&self, _point: &Point3D) -> Vector3 {
+        self.sun_direction()
+    }
+
+    fn distance(&self, _point: &Point3D) -> f64 {
+        f64::INFINITY
+    }
+
+    fn intensity(&self) -> f64 {
+        self.sun_intensity
+    }
+
+    fn intensity_at(&self, _point: &Point3D) -> f64 {
+        self.sun_intensity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_sky_is_blue_and_the_low_sun_is_red() {
+        let sky = SkyLight::new(45.0, 0.0);
+        let zenith = sky.radiance(&Vector3::J);
+        assert!(zenith.b > zenith.r);
+
+        let high = sky.sun_color();
+        let low = SkyLight::new(3.0, 0.0).sun_color();
+        assert!(low.r / low.b > high.r / high.b);
+        assert_eq!(SkyLight::new(-5.0, 0.0).sun_color(), Color::BLACK);
+        assert_eq!(Luminous::color(&sky), high);
+    }
+
+    #[test]
+    fn a_sunless_sky_still_matters() {
+        let sky = Light::from(SkyLight::new(30.0, 0.0).sun_intensity(0.0));
+        assert!(sky.importance(&Point3D::default()) > 0.0);
+        assert!(sky.sample_one(&Point3D::default()).is_some());
+    }
+
+    #[test]
+    fn the_sky_brightens_towards_the_sun() {
+        let sky = SkyLight::new(20.0, 90.0);
+        let towards = sky.radiance(&Vector3::new([1.0, 0.5, 0.0]));
+        let away = sky.radiance(&Vector3::new([-1.0, 0.5, 0.0]));
+        assert!(towards.g > away.g);
+
+        // the sun disk is far brighter than the sky around it
+        let sun = sky.radiance(&sky.sun_direction());
+        assert!(sun.g > 100.0 * towards.g);
+    }
+
+    #[test]
+    fn sun_samples_stay_on_the_disk() {
+        let sky = SkyLight::new(60.0, 30.0).sun_size(2.0).samples(32);
+        let sun = sky.sun_direction();
+        let samples = sky.sample_from(&Point3D::default());
+
+        let on_disk = samples
+            .iter()
+            .filter(|s| s.tint == sky.sun_color())
+            .collect::<Vec<_>>();
+        assert_eq!(on_disk.len(), 32);
+        assert!(on_disk
+            .iter()
+            .all(|s| s.direction.dot(sun) >= 1.0_f64.to_radians().cos() - 1e-9));
+
+        let irradiance = on_disk.iter().map(|s| s.intensity).sum::<f64>();
+        assert!((irradiance - 3.0).abs() < 1e-9);
+    }
+}
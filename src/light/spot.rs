@@ -0,0 +1,214 @@
+use std::f64::consts::PI;
+
+use crate::{
+    color::Color,
+    math::{Point3D, Vector3},
+    spectrum::Spectrum,
+};
+
+use super::{IesProfile, Light, Luminous};
+
+/// A point light that only shines within a cone. Light is at full strength
+/// inside the inner angle and fades out smoothly towards the outer angle.
+/// Both angles are measured from the axis of the cone, in degrees.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpotLight {
+    location: Point3D,
+    direction: Vector3,
+    inner_angle: f64,
+    outer_angle: f64,
+    color: Color,
+    intensity: f64,
+    profile: Option<IesProfile>,
+    // the direction of the profile's 0° horizontal angle
+    reference: Vector3,
+    spectrum: Option<Spectrum>,
+}
+
+impl SpotLight {
+    /// A spot light at `location` shining along `direction`, or straight
+    /// down if `direction` has no length.
+    pub fn new(location: Point3D, direction: Vector3) -> Self {
+        let direction = if direction.magnitude() < 1e-9 {
+            -Vector3::J
+        } else {
+            direction.normalize()
+        };
+
+        SpotLight {
+            location,
+            direction,
+            inner_angle: 30.0,
+            outer_angle: 45.0,
+            color: Color::WHITE,
+            intensity: 3000.0,
+            profile: None,
+            reference: Vector3::I,
+            spectrum: None,
+        }
+    }
+
+    /// A spot light at `location` aimed at `target`.
+    pub fn looking_at(location: Point3D, target: &Point3D) -> Self {
+        Self::new(location, target - location)
+    }
+
+    pub fn cone(mut self, inner_angle: f64, outer_angle: f64) -> Self {
+        self.inner_angle = inner_angle.min(outer_angle);
+        self.outer_angle = outer_angle;
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Shape the light by a measured photometric profile, with the fixture
+    /// pointing along the spot's direction and its 0° horizontal angle
+    /// towards +x unless turned with [orientation](Self::orientation). The
+    /// cone still applies.
+    pub fn profile(mut self, profile: IesProfile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    /// Point the spot, and the profile's axis with it, along `down`, with the
+    /// profile's 0° horizontal angle towards `reference`. A zero `down` is
+    /// ignored.
+    pub fn orientation(mut self, down: Vector3, reference: Vector3) -> Self {
+        if down.magnitude() > 1e-9 {
+            self.direction = down.normalize();
+        }
+        self.reference = reference;
+        self
+    }
+
+    pub fn spectrum(mut self, spectrum: Spectrum) -> Self {
+        self.spectrum = Some(spectrum);
+        self
+    }
+
+    /// How much of the light reaches `point`, from one inside the inner cone
+    /// to zero outside the outer cone. The light's own location counts as
+    /// inside.
+    pub fn falloff(&self, point: &Point3D) -> f64 {
+        let offset = point - self.location;
+        if offset.magnitude() < 1e-9 {
+            return 1.0;
+        }
+
+        let cos_angle = offset.normalize().dot(self.direction);
+        let cos_inner = self.inner_angle.to_radians().cos();
+        let cos_outer = self.outer_angle.to_radians().cos();
+
+        if cos_angle >= cos_inner {
+            return 1.0;
+        }
+        if cos_angle <= cos_outer {
+            return 0.0;
+        }
+
+        let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl From<SpotLight> for Light {
+    fn from(s: SpotLight) -> Self {
+        Light::Spot(s)
+    }
+}
+
+impl Luminous for SpotLight {
+    fn color(&self) -> Color {
+        self.color
+    }
+
+    fn direction_from(&self, point: &Point3D) -> Vector3 {
+        (self.location - point).normalize()
+    }
+
+    fn distance(&self, point: &Point3D) -> f64 {
+        self.location.dist(point)
+    }
+
+    fn intensity(&self) -> f64 {
+        self.intensity
+    }
+
+    fn intensity_at(&self, point: &Point3D) -> f64 {
+        let r2 = (self.location - point).norm();
+        if r2 < 1e-6_f64 {
+            return self.intensity();
+        }
+
+        let shape = self.profile.as_ref().map_or(1.0, |profile| {
+            profile.scale(&(point - self.location), &self.direction, &self.reference)
+        });
+
+        shape * self.falloff(point) * self.intensity() / (4.0 * PI * r2)
+    }
+
+    fn spectrum(&self) -> Option<Spectrum> {
+        self.spectrum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falloff_between_cones() {
+        let spot = SpotLight::new(Point3D::default(), -Vector3::J).cone(20.0, 40.0);
+        let at = |degrees: f64| {
+            let (sin, cos) = degrees.to_radians().sin_cos();
+            spot.falloff(&Point3D::new(sin, -cos, 0.0))
+        };
+
+        assert_eq!(at(0.0), 1.0);
+        assert_eq!(at(19.0), 1.0);
+        assert!(at(25.0) < 1.0 && at(25.0) > at(35.0) && at(35.0) > 0.0);
+        assert_eq!(at(41.0), 0.0);
+        assert_eq!(at(120.0), 0.0);
+    }
+
+    #[test]
+    fn degenerate_directions_stay_finite() {
+        let location = Point3D::new(0.0, 2.0, 0.0);
+        let spot = SpotLight::looking_at(location, &location);
+
+        assert_eq!(spot.falloff(&Point3D::default()), 1.0);
+        assert_eq!(spot.falloff(&location), 1.0);
+        assert!(spot.intensity_at(&Point3D::default()).is_finite());
+    }
+
+    #[test]
+    fn profiles_follow_the_orientation() {
+        // lit off the axis only towards 0° and 180° horizontal
+        let profile = IesProfile::parse(
+            "TILT=NONE\n1 -1 1 3 2 1 1 0 0 0\n1 1 0\n0 45 90\n0 90\n100 100 100\n100 0 0\n",
+        )
+        .unwrap();
+        let spot = SpotLight::new(Point3D::default(), -Vector3::J)
+            .cone(80.0, 85.0)
+            .profile(profile);
+        let turned = spot.clone().orientation(-Vector3::J, Vector3::K);
+        let sideways = spot.clone().orientation(Vector3::I, Vector3::J);
+
+        let towards_x = Point3D::new(1.0, -1.0, 0.0);
+        let towards_z = Point3D::new(0.0, -1.0, 1.0);
+        assert!(spot.intensity_at(&towards_x) > spot.intensity_at(&towards_z));
+        assert!(turned.intensity_at(&towards_z) > turned.intensity_at(&towards_x));
+
+        // turning the fixture aims the cone as well
+        assert!(sideways.intensity_at(&Point3D::new(1.0, 0.0, 0.0)) > 0.0);
+        assert_eq!(sideways.intensity_at(&Point3D::new(0.0, -1.0, 0.0)), 0.0);
+    }
+}
@@ -0,0 +1,74 @@
+/// Describes how the refractive index of a dielectric varies with wavelength.
+/// Coefficients follow the usual conventions with wavelengths in micrometers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dispersion {
+    /// `n = a + b / λ²`
+    Cauchy { a: f64, b: f64 },
+    /// `n² = 1 + Σ bᵢλ² / (λ² - cᵢ)`
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    /// Schott BK7, a common crown glass.
+    pub fn bk7() -> Self {
+        Dispersion::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    /// Dense flint glass, which disperses much more strongly than crown glass.
+    pub fn flint() -> Self {
+        Dispersion::Cauchy {
+            a: 1.7280,
+            b: 0.01342,
+        }
+    }
+
+    pub fn diamond() -> Self {
+        Dispersion::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.030625, 0.011236, 0.0],
+        }
+    }
+
+    /// The refractive index at `wavelength` (in nm).
+    pub fn index_at(&self, wavelength: f64) -> f64 {
+        let l = wavelength / 1000.0;
+        let l2 = l * l;
+
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => (1.0
+                + b.iter()
+                    .zip(c.iter())
+                    .map(|(b, c)| b * l2 / (l2 - c))
+                    .sum::<f64>())
+            .sqrt(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bk7_index() {
+        // reference value at the sodium D line
+        assert!((Dispersion::bk7().index_at(587.6) - 1.5168).abs() < 1e-4);
+    }
+
+    #[test]
+    fn blue_bends_more_than_red() {
+        for d in [
+            Dispersion::bk7(),
+            Dispersion::flint(),
+            Dispersion::diamond(),
+        ]
+        .iter()
+        {
+            assert!(d.index_at(450.0) > d.index_at(650.0));
+        }
+    }
+}
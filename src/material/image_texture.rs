@@ -0,0 +1,553 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use image::{codecs::hdr::HdrDecoder, DynamicImage, GenericImageView};
+
+use crate::{
+    color::Color,
+    error::Result,
+    math::{Point2D, Vector2},
+};
+
+use super::{Texture, TextureCoord, UvTransform, WrapMode};
+
+/// The most texels an anisotropic lookup will average along its long axis.
+const MAX_ANISOTROPY: f64 = 16.0;
+
+/// How an [ImageTexture] is sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// The closest texel, which is fast but blocky up close and shimmers far
+    /// away.
+    Nearest,
+    /// Blend the four closest texels.
+    Bilinear,
+    /// Bilinear lookups in the two mip levels closest to the pixel footprint,
+    /// blended together.
+    Trilinear,
+    /// Several trilinear lookups along the long axis of the pixel footprint,
+    /// weighted by a Gaussian, which keeps surfaces seen at grazing angles
+    /// sharp where trilinear filtering blurs them.
+    Anisotropic,
+}
+
+impl Filter {
+    fn uses_mipmaps(&self) -> bool {
+        matches!(self, Filter::Trilinear | Filter::Anisotropic)
+    }
+}
+
+/// How the values stored in an image relate to light intensity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Gamma encoded, as used by most 8 and 16-bit color images.
+    Srgb,
+    /// Stored as is: HDR images, and data such as normal or height maps.
+    Linear,
+}
+
+impl ColorSpace {
+    fn decode(&self, v: f32) -> f32 {
+        match self {
+            ColorSpace::Linear => v,
+            ColorSpace::Srgb if v <= 0.04045 => v / 12.92,
+            ColorSpace::Srgb => ((v + 0.055) / 1.055).powf(2.4),
+        }
+    }
+
+    fn encode(&self, v: f32) -> f32 {
+        match self {
+            ColorSpace::Linear => v,
+            ColorSpace::Srgb if v <= 0.0031308 => v * 12.92,
+            ColorSpace::Srgb => 1.055 * v.powf(1.0 / 2.4) - 0.055,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MipLevel {
+    width: usize,
+    height: usize,
+    texels: Vec<[f32; 4]>,
+}
+
+impl MipLevel {
+    fn texel(&self, x: i64, y: i64, wrap: &WrapMode) -> Color {
+        match (wrap.texel(x, self.width), wrap.texel(y, self.height)) {
+            (Some(x), Some(y)) => {
+                let [r, g, b, a] = self.texels[y * self.width + x];
+                Color::with_alpha(r as f64, g as f64, b as f64, a as f64)
+            }
+            _ => wrap.border(),
+        }
+    }
+
+    fn nearest(&self, u: f64, v: f64, wrap: &WrapMode) -> Color {
+        let x = (u * self.width as f64).floor() as i64;
+        let y = (v * self.height as f64).floor() as i64;
+        self.texel(x, y, wrap)
+    }
+
+    fn bilinear(&self, u: f64, v: f64, wrap: &WrapMode) -> Color {
+        // texel centers sit at half integer coordinates
+        let x = u * self.width as f64 - 0.5;
+        let y = v * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self
+            .texel(x0, y0, wrap)
+            .mix(self.texel(x0 + 1, y0, wrap), tx);
+        let bottom = self
+            .texel(x0, y0 + 1, wrap)
+            .mix(self.texel(x0 + 1, y0 + 1, wrap), tx);
+        top.mix(bottom, ty)
+    }
+
+    /// Halve the resolution, averaging blocks of four texels. With an odd
+    /// size the last blocks also take in the leftover row or column, so every
+    /// texel counts towards the next level.
+    fn downsample(&self) -> MipLevel {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut texels = Vec::with_capacity(width * height);
+
+        // the source texels covered by one output texel along an axis
+        let span = |i: usize, out: usize, size: usize| {
+            let end = if i + 1 == out { size } else { 2 * i + 2 };
+            2 * i..end
+        };
+
+        for y in 0..height {
+            let rows = span(y, height, self.height);
+            for x in 0..width {
+                let cols = span(x, width, self.width);
+                let count = (rows.len() * cols.len()) as f32;
+
+                let mut sum = [0.0; 4];
+                for sy in rows.clone() {
+                    for sx in cols.clone() {
+                        let texel = self.texels[sy * self.width + sx];
+                        for (s, t) in sum.iter_mut().zip(texel.iter()) {
+                            *s += t / count;
+                        }
+                    }
+                }
+                texels.push(sum);
+            }
+        }
+
+        MipLevel {
+            width,
+            height,
+            texels,
+        }
+    }
+}
+
+/// An image used as a texture. Each image texture has its own wrap mode and
+/// [UvTransform], applied after the material's scale.
+///
+/// Example:
+/// ```no_run
+/// use image::io::Reader as ImageReader;
+/// use raytracer_rs::material::{Filter, ImageTexture, Material};
+///
+/// let image = ImageReader::open("wood.png").unwrap().decode().unwrap();
+/// let floor = Material::new(ImageTexture::new(image).with_filter(Filter::Trilinear));
+/// ```
+///
+/// A label placed once on a mesh rather than tiled:
+/// ```no_run
+/// use image::io::Reader as ImageReader;
+/// use raytracer_rs::color::Color;
+/// use raytracer_rs::material::{ImageTexture, Material, UvTransform, WrapMode};
+///
+/// let image = ImageReader::open("label.png").unwrap().decode().unwrap();
+/// let label = ImageTexture::new(image)
+///     .with_wrap(WrapMode::ClampToBorder(Color::WHITE))
+///     .with_transform(UvTransform::new().with_scale(4.0, 2.0).with_offset(-1.5, -0.5));
+/// let bottle = Material::new(label);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageTexture {
+    levels: Vec<MipLevel>,
+    color_space: ColorSpace,
+    filter: Filter,
+    wrap: WrapMode,
+    transform: UvTransform,
+}
+
+impl ImageTexture {
+    /// A color image, taken to be sRGB encoded.
+    pub fn new(image: DynamicImage) -> Self {
+        Self::with_color_space(image, ColorSpace::Srgb)
+    }
+
+    /// An image whose texels are converted to linear values from
+    /// `color_space` as it's loaded. 16-bit images keep their full precision.
+    pub fn with_color_space(image: DynamicImage, color_space: ColorSpace) -> Self {
+        let (width, height) = image.dimensions();
+        let decode = |v: f32| color_space.decode(v);
+
+        let texels = match image {
+            DynamicImage::ImageLuma16(_)
+            | DynamicImage::ImageLumaA16(_)
+            | DynamicImage::ImageRgb16(_)
+            | DynamicImage::ImageRgba16(_) => image
+                .to_rgba16()
+                .pixels()
+                .map(|p| {
+                    let c = |v: u16| v as f32 / 65535.0;
+                    [decode(c(p[0])), decode(c(p[1])), decode(c(p[2])), c(p[3])]
+                })
+                .collect(),
+            _ => image
+                .to_rgba8()
+                .pixels()
+                .map(|p| {
+                    let c = |v: u8| v as f32 / 255.0;
+                    [decode(c(p[0])), decode(c(p[1])), decode(c(p[2])), c(p[3])]
+                })
+                .collect(),
+        };
+
+        Self::from_texels(width as usize, height as usize, texels, color_space)
+    }
+
+    /// A floating point image in linear RGB, such as one read from a Radiance
+    /// `.hdr` file. Values are not clamped, so bright texels stay bright.
+    pub fn from_hdr(width: usize, height: usize, pixels: &[[f32; 3]]) -> Self {
+        let texels = pixels.iter().map(|&[r, g, b]| [r, g, b, 1.0]).collect();
+        Self::from_texels(width, height, texels, ColorSpace::Linear)
+    }
+
+    /// Load an image from disk. Radiance `.hdr` files are read as floating
+    /// point, everything else as an sRGB color image.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let is_hdr = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("hdr"));
+
+        if is_hdr {
+            let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+            let meta = decoder.metadata();
+            let pixels = decoder
+                .read_image_hdr()?
+                .iter()
+                .map(|p| p.0)
+                .collect::<Vec<[f32; 3]>>();
+
+            Ok(Self::from_hdr(
+                meta.width as usize,
+                meta.height as usize,
+                &pixels,
+            ))
+        } else {
+            Ok(Self::new(image::open(path)?))
+        }
+    }
+
+    fn from_texels(
+        width: usize,
+        height: usize,
+        texels: Vec<[f32; 4]>,
+        color_space: ColorSpace,
+    ) -> Self {
+        ImageTexture {
+            levels: vec![MipLevel {
+                width: width.max(1),
+                height: height.max(1),
+                texels,
+            }],
+            color_space,
+            filter: Filter::Nearest,
+            wrap: WrapMode::Repeat,
+            transform: UvTransform::new(),
+        }
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn with_transform(mut self, transform: UvTransform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Choose how the image is sampled, building the mip pyramid if the
+    /// filter needs one.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        if filter.uses_mipmaps() {
+            self.build_mipmaps();
+        }
+        self
+    }
+
+    /// Take the texels as they were stored, undoing any decoding, for images
+    /// that hold data such as normals or heights rather than color.
+    pub(crate) fn into_linear(mut self) -> Self {
+        if self.color_space == ColorSpace::Linear {
+            return self;
+        }
+
+        let color_space = self.color_space;
+        self.levels.truncate(1);
+        for texel in self.levels[0].texels.iter_mut() {
+            for v in texel.iter_mut().take(3) {
+                *v = color_space.encode(*v);
+            }
+        }
+
+        self.color_space = ColorSpace::Linear;
+        if self.filter.uses_mipmaps() {
+            self.build_mipmaps();
+        }
+        self
+    }
+
+    /// The color space the image was stored in. Texels are always linear once
+    /// loaded.
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    pub fn width(&self) -> usize {
+        self.levels[0].width
+    }
+
+    pub fn height(&self) -> usize {
+        self.levels[0].height
+    }
+
+    /// The number of mip levels, including the full resolution image.
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    fn build_mipmaps(&mut self) {
+        self.levels.truncate(1);
+        loop {
+            let last = &self.levels[self.levels.len() - 1];
+            if last.width == 1 && last.height == 1 {
+                break;
+            }
+            let next = last.downsample();
+            self.levels.push(next);
+        }
+    }
+
+    pub fn color(&self, coord: &TextureCoord) -> Color {
+        let uv = self.transform.apply(&Point2D::new(
+            coord.uv.x() * coord.scale,
+            coord.uv.y() * coord.scale,
+        ));
+        let (u, v) = (uv.x(), uv.y());
+
+        match self.filter {
+            Filter::Nearest => self.levels[0].nearest(u, v, &self.wrap),
+            Filter::Bilinear => self.levels[0].bilinear(u, v, &self.wrap),
+            Filter::Trilinear => {
+                let (dx, dy) = self.footprint(coord);
+                let width = dx.magnitude().max(dy.magnitude());
+                self.trilinear(u, v, width.max(1.0).log2())
+            }
+            Filter::Anisotropic => {
+                let (dx, dy) = self.footprint(coord);
+                let (major, minor) = if dx.magnitude() >= dy.magnitude() {
+                    (dx, dy)
+                } else {
+                    (dy, dx)
+                };
+
+                let major_len = major.magnitude();
+                if major_len <= 1.0 {
+                    return self.levels[0].bilinear(u, v, &self.wrap);
+                }
+
+                // clamp the eccentricity by blurring along the short axis
+                // instead, so lookups stay bounded
+                let ratio = (major_len / minor.magnitude().max(1e-12)).min(MAX_ANISOTROPY);
+                let lod = (major_len / ratio).max(1.0).log2();
+                let samples = ratio.ceil() as usize;
+
+                // the step along the long axis, back in uv units
+                let axis = Vector2::new([
+                    major.x() / self.width() as f64,
+                    major.y() / self.height() as f64,
+                ]);
+
+                let (color, total) = (0..samples).fold((Color::BLACK, 0.0), |(acc, total), i| {
+                    let t = (i as f64 + 0.5) / samples as f64 - 0.5;
+                    let weight = (-8.0 * t * t).exp();
+                    let c = self.trilinear(u + axis.x() * t, v + axis.y() * t, lod);
+                    (acc + c * weight, total + weight)
+                });
+
+                color * (1.0 / total)
+            }
+        }
+    }
+
+    /// The pixel footprint along the screen's x and y axes, in texels of the
+    /// full resolution image.
+    fn footprint(&self, coord: &TextureCoord) -> (Vector2, Vector2) {
+        let to_texels = |d: Vector2| {
+            let d = self.transform.apply_vector(&(d * coord.scale));
+            Vector2::new([d.x() * self.width() as f64, d.y() * self.height() as f64])
+        };
+
+        (to_texels(coord.duv_dx), to_texels(coord.duv_dy))
+    }
+
+    fn trilinear(&self, u: f64, v: f64, lod: f64) -> Color {
+        let lod = lod.clamp(0.0, (self.levels.len() - 1) as f64);
+        let fine = lod.floor() as usize;
+        let coarse = (fine + 1).min(self.levels.len() - 1);
+
+        self.levels[fine].bilinear(u, v, &self.wrap).mix(
+            self.levels[coarse].bilinear(u, v, &self.wrap),
+            lod - fine as f64,
+        )
+    }
+}
+
+impl From<ImageTexture> for Texture {
+    fn from(i: ImageTexture) -> Self {
+        Texture::Image(i)
+    }
+}
+
+impl From<DynamicImage> for Texture {
+    fn from(i: DynamicImage) -> Self {
+        Texture::Image(ImageTexture::new(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+    use crate::material::Material;
+
+    /// A black and white checkerboard with single texel squares.
+    fn checkerboard(size: u32) -> ImageTexture {
+        let image = RgbaImage::from_fn(size, size, |x, y| {
+            if (x + y) % 2 == 0 {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        });
+
+        ImageTexture::new(DynamicImage::ImageRgba8(image))
+    }
+
+    #[test]
+    fn mip_pyramid_goes_down_to_one_texel() {
+        let texture = checkerboard(8).with_filter(Filter::Trilinear);
+        assert_eq!(texture.levels(), 4);
+
+        let top = &texture.levels[3];
+        assert_eq!((top.width, top.height), (1, 1));
+        assert!((top.texels[0][0] - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn bilinear_blends_neighbouring_texels() {
+        let texture = checkerboard(4).with_filter(Filter::Bilinear);
+        // exactly between four texels, two of each color
+        let c = texture.color(&TextureCoord::new(Point2D::new(0.25, 0.25), 1.0));
+        assert!((c.r - 0.5).abs() < 1e-6);
+
+        // on a texel center
+        let c = texture.color(&TextureCoord::new(Point2D::new(0.125, 0.125), 1.0));
+        assert!((c.r - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn color_spaces() {
+        let gray =
+            || DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([128, 128, 128, 128])));
+        let coord = TextureCoord::new(Point2D::default(), 1.0);
+
+        let srgb = ImageTexture::new(gray()).color(&coord);
+        assert!((srgb.r - 0.2158).abs() < 1e-3);
+        // alpha is always linear
+        assert!((srgb.a - 128.0 / 255.0).abs() < 1e-6);
+
+        let linear = ImageTexture::with_color_space(gray(), ColorSpace::Linear).color(&coord);
+        assert!((linear.r - 128.0 / 255.0).abs() < 1e-6);
+        // a bare image is a color image too
+        let bare = Material::new(gray()).texture.color(&coord);
+        assert!((bare.r - srgb.r).abs() < 1e-6);
+        let data = ImageTexture::new(gray()).into_linear().color(&coord);
+        assert!((data.r - 128.0 / 255.0).abs() < 1e-6);
+
+        let deep = image::ImageBuffer::from_pixel(1, 1, image::Rgb([65535u16, 0, 0]));
+        let deep = ImageTexture::new(DynamicImage::ImageRgb16(deep)).color(&coord);
+        assert_eq!((deep.r, deep.g), (1.0, 0.0));
+
+        let hdr = ImageTexture::from_hdr(1, 1, &[[12.5, 0.5, 0.0]]).color(&coord);
+        assert_eq!((hdr.r, hdr.g), (12.5, 0.5));
+    }
+
+    #[test]
+    fn wrap_modes() {
+        let size = 4;
+        assert_eq!(WrapMode::Repeat.texel(-1, size), Some(3));
+        assert_eq!(WrapMode::MirroredRepeat.texel(-1, size), Some(0));
+        assert_eq!(WrapMode::MirroredRepeat.texel(5, size), Some(2));
+        assert_eq!(WrapMode::ClampToEdge.texel(9, size), Some(3));
+        assert_eq!(WrapMode::ClampToBorder(Color::RED).texel(4, size), None);
+
+        let decal = checkerboard(4).with_wrap(WrapMode::ClampToBorder(Color::RED));
+        let inside = TextureCoord::new(Point2D::new(0.1, 0.1), 1.0);
+        let outside = TextureCoord::new(Point2D::new(1.1, 0.1), 1.0);
+        assert_eq!(decal.color(&inside).r, 1.0);
+        assert_eq!(decal.color(&outside), Color::RED);
+    }
+
+    #[test]
+    fn minified_lookups_average_out() {
+        let mut coord = TextureCoord::new(Point2D::new(0.3, 0.6), 1.0);
+        coord.duv_dx = Vector2::new([1.0, 0.0]);
+        coord.duv_dy = Vector2::new([0.0, 1.0]);
+
+        for filter in [Filter::Trilinear, Filter::Anisotropic].iter() {
+            let c = checkerboard(16).with_filter(*filter).color(&coord);
+            assert!((c.r - 0.5).abs() < 1e-6, "{:?} gave {:?}", filter, c);
+        }
+    }
+
+    #[test]
+    fn odd_sizes_keep_every_texel() {
+        let texels = vec![
+            [0.0, 0.0, 0.0, 1.0],
+            [0.3, 0.0, 0.0, 1.0],
+            [0.9, 0.0, 0.0, 1.0],
+        ];
+        let level = MipLevel {
+            width: 3,
+            height: 1,
+            texels,
+        }
+        .downsample();
+
+        assert_eq!((level.width, level.height), (1, 1));
+        assert!((level.texels[0][0] - 0.4).abs() < 1e-6);
+        assert!((level.texels[0][3] - 1.0).abs() < 1e-6);
+
+        let texture = ImageTexture::from_hdr(5, 3, &[[1.0, 0.0, 0.0]; 15]);
+        let level = texture.levels[0].downsample();
+        assert_eq!((level.width, level.height), (2, 1));
+        for texel in &level.texels {
+            assert!((texel[0] - 1.0).abs() < 1e-6);
+        }
+    }
+}
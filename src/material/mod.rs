@@ -1,11 +1,26 @@
 pub use crate::material::checker::Checker;
-pub use crate::material::texture::{Texture, TextureCoord};
+pub use crate::material::dispersion::Dispersion;
+pub use crate::material::image_texture::{ColorSpace, Filter, ImageTexture};
+pub use crate::material::node::{Node, UvTransform};
+pub use crate::material::noise::{Noise, Pattern};
+pub use crate::material::normal_map::NormalMap;
+pub use crate::material::principled::{Principled, PrincipledSample};
+pub use crate::material::texture::{Texture, TextureCoord, WrapMode};
+pub use crate::material::thin_film::{Substrate, ThinFilm};
 
 use crate::color::Color;
-use crate::math::Point3D;
+use crate::math::{Point3D, Vector3};
+use crate::spectrum::Spectrum;
 
 mod checker;
+mod dispersion;
+mod image_texture;
+mod node;
+mod noise;
+mod normal_map;
+mod principled;
 mod texture;
+mod thin_film;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Surface {
@@ -20,6 +35,30 @@ pub struct Material {
     pub surface: Surface,
     pub scale: f64,
     pub texture: Texture,
+    pub specular: Color,
+    pub specular_exponent: f64,
+    pub specular_weight: f64,
+    pub emission: Color,
+    pub emission_strength: f64,
+    pub emission_spectrum: Option<Spectrum>,
+    pub absorption: Color,
+    pub dispersion: Option<Dispersion>,
+    pub thin_film: Option<ThinFilm>,
+    pub normal_map: Option<NormalMap>,
+    pub opacity: Option<Texture>,
+    pub alpha_cutout: bool,
+    pub principled: Option<Principled>,
+    pub blend: Option<Box<Blend>>,
+}
+
+/// Two materials mixed together while shading, with `mask` deciding how much
+/// of `layer` shows over `base` (read from the red channel, like other scalar
+/// textures).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blend {
+    pub base: Material,
+    pub layer: Material,
+    pub mask: Texture,
 }
 
 impl Material {
@@ -29,6 +68,20 @@ impl Material {
             albedo: 1.0,
             surface: Surface::Diffuse,
             scale: 1.0,
+            specular: Color::WHITE,
+            specular_exponent: 32.0,
+            specular_weight: 0.0,
+            emission: Color::BLACK,
+            emission_strength: 0.0,
+            emission_spectrum: None,
+            absorption: Color::BLACK,
+            dispersion: None,
+            thin_film: None,
+            normal_map: None,
+            opacity: None,
+            alpha_cutout: false,
+            principled: None,
+            blend: None,
         }
     }
 
@@ -47,6 +100,267 @@ impl Material {
         self
     }
 
+    /// Add a Blinn-Phong highlight of the given color, `exponent` controlling
+    /// its tightness and `weight` its strength.
+    pub fn with_specular(mut self, color: Color, exponent: f64, weight: f64) -> Self {
+        self.specular = color;
+        self.specular_exponent = exponent;
+        self.specular_weight = weight;
+        self
+    }
+
+    /// The strength of the Blinn-Phong highlight for light arriving from
+    /// `light` and leaving towards `view`, both pointing away from the surface.
+    pub fn highlight(&self, normal: &Vector3, light: &Vector3, view: &Vector3) -> f64 {
+        if self.specular_weight <= 0.0 || normal.dot(*light) <= 0.0 {
+            return 0.0;
+        }
+
+        let half = (*light + *view).normalize();
+        normal.dot(half).max(0.0).powf(self.specular_exponent) * self.specular_weight
+    }
+
+    /// Make surfaces with this material glow. Emissive shapes are visible
+    /// without any lights and illuminate the rest of the scene.
+    pub fn with_emission(mut self, color: Color, strength: f64) -> Self {
+        self.emission = color;
+        self.emission_strength = strength;
+        self
+    }
+
+    /// Give the emitted light a spectrum, like [Light](crate::light::Light)s
+    /// can have, rather than upsampling the emission color in spectral mode.
+    pub fn with_emission_spectrum(mut self, spectrum: Spectrum) -> Self {
+        self.emission_spectrum = Some(spectrum);
+        self
+    }
+
+    pub fn emitted(&self) -> Color {
+        self.emission * self.emission_strength
+    }
+
+    /// The emitted light as seen by a ray of the given wavelength, including
+    /// the emission spectrum.
+    pub fn emitted_at(&self, wavelength: Option<f64>) -> Color {
+        let emitted = self.emitted();
+        match (self.emission_spectrum, wavelength) {
+            (Some(spectrum), Some(wavelength)) => {
+                emitted.at_wavelength(Some(wavelength)) * spectrum.value(wavelength)
+            }
+            (Some(spectrum), None) => emitted * spectrum.color(),
+            (None, _) => emitted.at_wavelength(wavelength),
+        }
+    }
+
+    /// The light emitted at `coord` as seen by a ray of the given wavelength,
+    /// following the mask of a blended material.
+    pub fn emission_at(&self, coord: &TextureCoord, wavelength: Option<f64>) -> Color {
+        match &self.blend {
+            Some(blend) => {
+                let mask = blend.mask.value(coord).clamp(0.0, 1.0);
+                blend
+                    .base
+                    .emission_at(coord, wavelength)
+                    .mix(blend.layer.emission_at(coord, wavelength), mask)
+            }
+            None => self.emitted_at(wavelength),
+        }
+    }
+
+    /// The brightest light any part of the surface emits, whatever the mask
+    /// of a blended material.
+    pub fn peak_emission(&self) -> Color {
+        match &self.blend {
+            Some(blend) => {
+                let (base, layer) = (blend.base.peak_emission(), blend.layer.peak_emission());
+                Color::new(
+                    base.r.max(layer.r),
+                    base.g.max(layer.g),
+                    base.b.max(layer.b),
+                )
+            }
+            None => self.emitted_at(None),
+        }
+    }
+
+    /// Whether any part of the surface emits light, including the layers of
+    /// a blended material.
+    pub fn is_emissive(&self) -> bool {
+        match &self.blend {
+            Some(blend) => blend.base.is_emissive() || blend.layer.is_emissive(),
+            None => {
+                self.emission_strength > 0.0
+                    && (self.emission.r > 0.0 || self.emission.g > 0.0 || self.emission.b > 0.0)
+            }
+        }
+    }
+
+    /// Set the per-channel absorption coefficient used by
+    /// [Surface::Refractive] materials. Light travelling `d` units through
+    /// the object is attenuated by `exp(-absorption * d)`.
+    pub fn with_absorption(mut self, absorption: Color) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    /// Set absorption such that white light has become `color` after travelling
+    /// `distance` units through the object. A distance that isn't positive
+    /// leaves the absorption unchanged.
+    pub fn with_absorption_color(self, color: Color, distance: f64) -> Self {
+        if distance <= 0.0 {
+            return self;
+        }
+
+        let coefficient = |c: f64| -c.max(1e-6).ln() / distance;
+        self.with_absorption(Color::new(
+            coefficient(color.r),
+            coefficient(color.g),
+            coefficient(color.b),
+        ))
+    }
+
+    /// The fraction of light that survives travelling `distance` units
+    /// through this material.
+    pub fn transmittance(&self, distance: f64) -> Color {
+        Color::new(
+            (-self.absorption.r * distance).exp(),
+            (-self.absorption.g * distance).exp(),
+            (-self.absorption.b * distance).exp(),
+        )
+    }
+
+    /// Make the refractive index of [Surface::Refractive] materials depend on
+    /// wavelength, splitting white light into its spectrum.
+    pub fn with_dispersion(mut self, dispersion: Dispersion) -> Self {
+        self.dispersion = Some(dispersion);
+        self
+    }
+
+    /// The refractive index seen by a ray of the given wavelength, falling back
+    /// to `index` for non-dispersive materials and untagged rays.
+    pub fn refractive_index(&self, index: f64, wavelength: Option<f64>) -> f64 {
+        match (self.dispersion, wavelength) {
+            (Some(dispersion), Some(wavelength)) => dispersion.index_at(wavelength),
+            _ => index,
+        }
+    }
+
+    /// Coat [Surface::Reflective] and [Surface::Refractive] materials with a
+    /// thin film, giving them iridescent reflections.
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Self {
+        self.thin_film = Some(film);
+        self
+    }
+
+    /// Add surface detail with a normal or bump map. The map holds data, so
+    /// its images are read as linear values rather than decoded from sRGB.
+    pub fn with_normal_map(mut self, map: impl Into<NormalMap>) -> Self {
+        self.normal_map = Some(map.into().into_linear());
+        self
+    }
+
+    /// Cut holes in the surface where `opacity` (read from the red channel) is
+    /// below one half. Rays pass straight through the holes. Like normal maps,
+    /// opacity images are read as linear values.
+    pub fn with_opacity(mut self, opacity: impl Into<Texture>) -> Self {
+        self.opacity = Some(opacity.into().into_linear());
+        self
+    }
+
+    /// Cut holes in the surface where the texture's alpha is below one half.
+    pub fn with_alpha_cutout(mut self) -> Self {
+        self.alpha_cutout = true;
+        self
+    }
+
+    pub fn has_cutout(&self) -> bool {
+        self.opacity.is_some() || self.alpha_cutout
+    }
+
+    /// Whether the surface is there at `coord`, or has been cut out.
+    pub fn is_opaque_at(&self, coord: &TextureCoord) -> bool {
+        let mut opacity = self.opacity.as_ref().map_or(1.0, |t| t.value(coord));
+        if self.alpha_cutout {
+            opacity *= self.texture.color(coord).a;
+        }
+
+        opacity >= 0.5
+    }
+
+    /// The fraction of light a shadow ray keeps when it passes through the
+    /// surface at `coord`, or `None` if the surface blocks it. Blended
+    /// materials mix the filters of their layers, with an opaque layer
+    /// letting nothing through.
+    pub fn shadow_filter(&self, coord: &TextureCoord) -> Option<Color> {
+        if let Some(blend) = &self.blend {
+            let mask = blend.mask.value(coord).clamp(0.0, 1.0);
+            if mask <= 0.0 {
+                return blend.base.shadow_filter(coord);
+            } else if mask >= 1.0 {
+                return blend.layer.shadow_filter(coord);
+            }
+
+            return match (
+                blend.base.shadow_filter(coord),
+                blend.layer.shadow_filter(coord),
+            ) {
+                (None, None) => None,
+                (base, layer) => Some(
+                    base.unwrap_or(Color::BLACK)
+                        .mix(layer.unwrap_or(Color::BLACK), mask),
+                ),
+            };
+        }
+
+        if let Some(principled) = &self.principled {
+            let transmission = principled.transmission.value(coord).clamp(0.0, 1.0);
+            return if transmission > 0.0 {
+                Some(principled.base_color.color(coord) * transmission)
+            } else {
+                None
+            };
+        }
+
+        match self.surface {
+            Surface::Refractive { transparency, .. } => Some(self.color(coord) * transparency),
+            _ => None,
+        }
+    }
+
+    /// Shade with a [Principled] BRDF instead of `albedo` and `surface`.
+    pub fn with_principled(mut self, principled: Principled) -> Self {
+        self.principled = Some(principled);
+        self
+    }
+
+    /// Put `layer` over this material wherever `mask` is non-zero. A constant
+    /// mask blends the two materials evenly across the surface.
+    ///
+    /// The shading, the emitted light and the shadow filter are blended, so an
+    /// emissive layer lights the scene and a glass layer lets light through
+    /// where the mask shows it. The normal map, opacity and absorption are
+    /// this material's, whatever the mask.
+    ///
+    /// Example:
+    /// ```
+    /// use raytracer_rs::color::Color;
+    /// use raytracer_rs::material::{Checker, Material, Surface};
+    ///
+    /// let paint = Material::new(Color::RED).with_surface(Surface::Reflective(0.2));
+    /// let rust = Material::new(Color::new(0.4, 0.2, 0.1));
+    /// let worn = paint.with_layer(rust, Checker::new(Color::WHITE).with_secondary(Color::BLACK));
+    /// ```
+    pub fn with_layer(self, layer: Material, mask: impl Into<Texture>) -> Self {
+        Material {
+            blend: Some(Box::new(Blend {
+                base: self.clone(),
+                layer,
+                mask: mask.into(),
+            })),
+            ..self
+        }
+    }
+
     pub fn color(&self, coord: &TextureCoord) -> Color {
         self.texture.color(coord)
     }
@@ -61,3 +375,157 @@ impl Default for Material {
 pub trait Textured {
     fn texture_coord(&self, point: &Point3D) -> TextureCoord;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Point2D;
+
+    #[test]
+    fn absorption_color_is_reached_at_reference_distance() {
+        let tint = Color::new(0.9, 0.5, 0.25);
+        let material = Material::default().with_absorption_color(tint, 2.0);
+        let t = material.transmittance(2.0);
+
+        assert!((t.r - tint.r).abs() < 1e-9);
+        assert!((t.g - tint.g).abs() < 1e-9);
+        assert!((t.b - tint.b).abs() < 1e-9);
+        assert_eq!(material.transmittance(0.0), Color::WHITE);
+
+        let unchanged = Material::default().with_absorption_color(tint, 0.0);
+        assert_eq!(unchanged.transmittance(1.0), Color::WHITE);
+    }
+
+    #[test]
+    fn emission_spectra_shape_emitted_light() {
+        let ember = Material::default()
+            .with_emission(Color::WHITE, 2.0)
+            .with_emission_spectrum(Spectrum::blackbody(1900.0));
+
+        let red = ember.emitted_at(Some(650.0)).r;
+        let blue = ember.emitted_at(Some(450.0)).r;
+        assert!(red > 2.0 * blue);
+
+        let rgb = ember.emitted_at(None);
+        assert!(rgb.r > rgb.b);
+        assert_eq!(
+            Material::default()
+                .with_emission(Color::RED, 2.0)
+                .emitted_at(None),
+            Color::new(2.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn highlights_peak_at_the_mirror_direction() {
+        let shiny = Material::default().with_specular(Color::WHITE, 32.0, 0.5);
+        let normal = Vector3::J;
+        let light = Vector3::new([1.0, 1.0, 0.0]).normalize();
+        let mirror = Vector3::new([-1.0, 1.0, 0.0]).normalize();
+
+        assert!((shiny.highlight(&normal, &light, &mirror) - 0.5).abs() < 1e-12);
+        for view in [
+            Vector3::J,
+            Vector3::new([-1.0, 0.5, 0.3]).normalize(),
+            light,
+        ]
+        .iter()
+        {
+            assert!(shiny.highlight(&normal, &light, view) < 0.5);
+        }
+
+        // lights behind the surface give no highlight
+        assert_eq!(shiny.highlight(&normal, &-light, &mirror), 0.0);
+        assert_eq!(Material::default().highlight(&normal, &light, &mirror), 0.0);
+    }
+
+    #[test]
+    fn data_maps_are_read_as_linear() {
+        use image::{DynamicImage, Rgba, RgbaImage};
+
+        let image = |c: [u8; 4]| DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba(c)));
+        let coord = TextureCoord::new(Point2D::new(0.5, 0.5), 1.0);
+
+        // sRGB decoding would take this well below one half
+        let fence = Material::default().with_opacity(image([130, 130, 130, 255]));
+        assert!(fence.is_opaque_at(&coord));
+
+        let flat = Material::default()
+            .with_normal_map(NormalMap::Normal(image([128, 128, 255, 255]).into()));
+        let n = flat
+            .normal_map
+            .unwrap()
+            .perturb(&Vector3::J, &Vector3::I, &coord);
+        assert!((n - Vector3::J).magnitude() < 1e-2);
+    }
+
+    #[test]
+    fn cutouts_follow_opacity_and_alpha() {
+        let coord = TextureCoord::new(Point2D::new(0.2, 0.7), 1.0);
+        assert!(Material::default().is_opaque_at(&coord));
+
+        let fence = Material::default().with_opacity(0.25);
+        assert!(fence.has_cutout());
+        assert!(!fence.is_opaque_at(&coord));
+
+        let leaf = Material::new(Color::with_alpha(0.1, 0.6, 0.1, 0.0));
+        assert!(leaf.is_opaque_at(&coord));
+        assert!(!leaf.with_alpha_cutout().is_opaque_at(&coord));
+    }
+
+    #[test]
+    fn blended_shadow_filters_mix() {
+        let coord = TextureCoord::new(Point2D::new(0.5, 0.5), 1.0);
+        let glass = Material::new(Color::new(0.8, 1.0, 0.8)).with_surface(Surface::Refractive {
+            index: 1.5,
+            transparency: 1.0,
+        });
+        let stone = Material::default();
+
+        assert_eq!(
+            stone
+                .clone()
+                .with_layer(glass.clone(), 0.5)
+                .shadow_filter(&coord),
+            Some(Color::new(0.4, 0.5, 0.4))
+        );
+        assert_eq!(
+            glass
+                .clone()
+                .with_layer(stone.clone(), 0.0)
+                .shadow_filter(&coord),
+            glass.shadow_filter(&coord)
+        );
+        assert_eq!(
+            glass.with_layer(stone.clone(), 1.0).shadow_filter(&coord),
+            None
+        );
+        assert_eq!(
+            stone.clone().with_layer(stone, 0.5).shadow_filter(&coord),
+            None
+        );
+    }
+
+    #[test]
+    fn only_transparent_surfaces_let_shadows_through() {
+        let coord = TextureCoord::new(Point2D::new(0.5, 0.5), 1.0);
+        assert_eq!(Material::default().shadow_filter(&coord), None);
+
+        let glass = Material::new(Color::new(0.8, 1.0, 0.8)).with_surface(Surface::Refractive {
+            index: 1.5,
+            transparency: 0.5,
+        });
+        assert_eq!(glass.shadow_filter(&coord), Some(Color::new(0.4, 0.5, 0.4)));
+    }
+
+    #[test]
+    fn transmissive_principled_materials_tint_shadows() {
+        let coord = TextureCoord::new(Point2D::default(), 1.0);
+        let glass = Material::new(Color::WHITE)
+            .with_principled(Principled::new(Color::RED).with_transmission(0.5));
+        assert_eq!(glass.shadow_filter(&coord), Some(Color::RED * 0.5));
+
+        let plastic = Material::new(Color::WHITE).with_principled(Principled::new(Color::RED));
+        assert_eq!(plastic.shadow_filter(&coord), None);
+    }
+}
@@ -0,0 +1,304 @@
+use crate::{
+    color::Color,
+    math::{Point2D, Vector2},
+};
+
+use super::{Texture, TextureCoord};
+
+/// Maps texture coordinates before a texture is sampled: scale first, then
+/// rotate (in degrees, counter clockwise) and finally offset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvTransform {
+    pub offset: Vector2,
+    pub rotation: f64,
+    pub scale: Vector2,
+}
+
+impl UvTransform {
+    pub fn new() -> Self {
+        UvTransform {
+            offset: Vector2::ZERO,
+            rotation: 0.0,
+            scale: Vector2::new([1.0, 1.0]),
+        }
+    }
+
+    pub fn with_offset(mut self, u: f64, v: f64) -> Self {
+        self.offset = Vector2::new([u, v]);
+        self
+    }
+
+    pub fn with_rotation(mut self, degrees: f64) -> Self {
+        self.rotation = degrees;
+        self
+    }
+
+    pub fn with_scale(mut self, u: f64, v: f64) -> Self {
+        self.scale = Vector2::new([u, v]);
+        self
+    }
+
+    pub fn apply(&self, uv: &Point2D) -> Point2D {
+        (self.apply_vector(&Vector2::from(uv)) + self.offset).into()
+    }
+
+    /// Transform a difference between texture coordinates, which ignores the
+    /// offset.
+    pub fn apply_vector(&self, d: &Vector2) -> Vector2 {
+        let (u, v) = (d.x() * self.scale.x(), d.y() * self.scale.y());
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+
+        Vector2::new([u * cos - v * sin, u * sin + v * cos])
+    }
+}
+
+impl Default for UvTransform {
+    fn default() -> Self {
+        UvTransform::new()
+    }
+}
+
+/// Combines other textures. Nodes take textures as inputs, and are textures
+/// themselves, so they can be nested into small graphs.
+///
+/// Example:
+/// ```
+/// use raytracer_rs::color::Color;
+/// use raytracer_rs::material::{Checker, Material, Node, Noise, Pattern, UvTransform};
+///
+/// let tiles = Node::transform(
+///     Checker::new(Color::WHITE),
+///     UvTransform::new().with_rotation(45.0),
+/// );
+/// let dirt = Node::ramp(
+///     Noise::new(Pattern::Fbm).with_scale(8.0),
+///     vec![(0.3, Color::WHITE), (0.7, Color::new(0.4, 0.3, 0.2))],
+/// );
+///
+/// let floor = Material::new(Node::multiply(tiles, dirt));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Multiply(Texture, Texture),
+    Add(Texture, Texture),
+    /// Blend from the first texture to the second by the value of `mask`.
+    Mix(Texture, Texture, Texture),
+    /// Map the value of a texture through a gradient between color stops.
+    Ramp(Texture, Vec<(f64, Color)>),
+    Transform(Texture, UvTransform),
+    /// Project a texture along the three axes and blend by the normal, for
+    /// surfaces without a usable UV mapping. Higher `sharpness` narrows the
+    /// seams between projections.
+    Triplanar {
+        texture: Texture,
+        sharpness: f64,
+    },
+}
+
+impl Node {
+    pub fn multiply(a: impl Into<Texture>, b: impl Into<Texture>) -> Self {
+        Node::Multiply(a.into(), b.into())
+    }
+
+    pub fn add(a: impl Into<Texture>, b: impl Into<Texture>) -> Self {
+        Node::Add(a.into(), b.into())
+    }
+
+    pub fn mix(a: impl Into<Texture>, b: impl Into<Texture>, mask: impl Into<Texture>) -> Self {
+        Node::Mix(a.into(), b.into(), mask.into())
+    }
+
+    /// The stops are `(position, color)` pairs and are sorted by position.
+    pub fn ramp(input: impl Into<Texture>, mut stops: Vec<(f64, Color)>) -> Self {
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        Node::Ramp(input.into(), stops)
+    }
+
+    pub fn transform(input: impl Into<Texture>, transform: UvTransform) -> Self {
+        Node::Transform(input.into(), transform)
+    }
+
+    pub fn triplanar(texture: impl Into<Texture>, sharpness: f64) -> Self {
+        Node::Triplanar {
+            texture: texture.into(),
+            sharpness,
+        }
+    }
+
+    pub(crate) fn into_linear(self) -> Self {
+        match self {
+            Node::Multiply(a, b) => Node::Multiply(a.into_linear(), b.into_linear()),
+            Node::Add(a, b) => Node::Add(a.into_linear(), b.into_linear()),
+            Node::Mix(a, b, mask) => {
+                Node::Mix(a.into_linear(), b.into_linear(), mask.into_linear())
+            }
+            Node::Ramp(input, stops) => Node::Ramp(input.into_linear(), stops),
+            Node::Transform(input, transform) => Node::Transform(input.into_linear(), transform),
+            Node::Triplanar { texture, sharpness } => Node::Triplanar {
+                texture: texture.into_linear(),
+                sharpness,
+            },
+        }
+    }
+
+    pub fn color(&self, coord: &TextureCoord) -> Color {
+        match self {
+            Node::Multiply(a, b) => a.color(coord) * b.color(coord),
+            Node::Add(a, b) => a.color(coord) + b.color(coord),
+            Node::Mix(a, b, mask) => {
+                let t = mask.value(coord).clamp(0.0, 1.0);
+                a.color(coord).mix(b.color(coord), t)
+            }
+            Node::Ramp(input, stops) => ramp(stops, input.value(coord)),
+            Node::Transform(input, transform) => input.color(&TextureCoord {
+                uv: transform.apply(&coord.uv),
+                duv_dx: transform.apply_vector(&coord.duv_dx),
+                duv_dy: transform.apply_vector(&coord.duv_dy),
+                ..*coord
+            }),
+            Node::Triplanar { texture, sharpness } => {
+                let p = coord.position;
+                let n = coord.normal;
+                let weights = [
+                    n.x().abs().powf(*sharpness),
+                    n.y().abs().powf(*sharpness),
+                    n.z().abs().powf(*sharpness),
+                ];
+                let total = weights.iter().sum::<f64>();
+                if total <= 0.0 {
+                    return texture.color(coord);
+                }
+
+                let projections = [
+                    Point2D::new(p.z(), p.y()),
+                    Point2D::new(p.x(), p.z()),
+                    Point2D::new(p.x(), p.y()),
+                ];
+
+                projections
+                    .iter()
+                    .zip(weights.iter())
+                    .filter(|(_, w)| **w > 0.0)
+                    .fold(Color::BLACK, |acc, (uv, w)| {
+                        // the footprint follows the surface's own mapping, not
+                        // the projection, so projections are sampled unfiltered
+                        let projected = TextureCoord {
+                            uv: *uv,
+                            duv_dx: Vector2::ZERO,
+                            duv_dy: Vector2::ZERO,
+                            ..*coord
+                        };
+                        acc + texture.color(&projected) * (w / total)
+                    })
+            }
+        }
+    }
+}
+
+fn ramp(stops: &[(f64, Color)], t: f64) -> Color {
+    let (first, last) = match (stops.first(), stops.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Color::BLACK,
+    };
+
+    if t <= first.0 {
+        return first.1;
+    }
+
+    stops
+        .windows(2)
+        .find(|pair| t <= pair[1].0)
+        .map(|pair| {
+            let (a, b) = (pair[0], pair[1]);
+            let width = b.0 - a.0;
+            if width <= 0.0 {
+                b.1
+            } else {
+                a.1.mix(b.1, (t - a.0) / width)
+            }
+        })
+        .unwrap_or(last.1)
+}
+
+impl From<Node> for Texture {
+    fn from(n: Node) -> Self {
+        Texture::Node(Box::new(n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    use super::*;
+    use crate::{
+        material::{Filter, ImageTexture},
+        math::Vector3,
+    };
+
+    fn coord(u: f64, v: f64) -> TextureCoord {
+        TextureCoord::new(Point2D::new(u, v), 1.0)
+    }
+
+    #[test]
+    fn ramps_interpolate_between_stops() {
+        let node = Node::ramp(0.75, vec![(1.0, Color::WHITE), (0.5, Color::BLACK)]);
+        let c = node.color(&coord(0.0, 0.0));
+        assert!((c.r - 0.5).abs() < 1e-9);
+
+        let below = Node::ramp(0.1, vec![(0.5, Color::RED), (1.0, Color::BLUE)]);
+        assert_eq!(below.color(&coord(0.0, 0.0)), Color::RED);
+    }
+
+    #[test]
+    fn transforms_apply_in_order() {
+        let t = UvTransform::new()
+            .with_scale(2.0, 1.0)
+            .with_rotation(90.0)
+            .with_offset(1.0, 0.0);
+        let uv = t.apply(&Point2D::new(1.0, 0.0));
+        assert!((uv.x() - 1.0).abs() < 1e-9);
+        assert!((uv.y() - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn triplanar_picks_the_facing_projection() {
+        let checker = crate::material::Checker::new(Color::WHITE).with_secondary(Color::BLACK);
+        let node = Node::triplanar(checker.clone(), 4.0);
+
+        let mut c = coord(0.0, 0.0);
+        c.position = crate::math::Point3D::new(0.75, 0.0, 0.25);
+        c.normal = Vector3::J;
+
+        let expected = checker.color(&TextureCoord {
+            uv: Point2D::new(0.75, 0.25),
+            ..c
+        });
+        assert_eq!(node.color(&c), expected);
+    }
+
+    #[test]
+    fn transforms_scale_the_footprint() {
+        let image = RgbaImage::from_fn(16, 16, |x, y| {
+            let c = if (x + y) % 2 == 0 { 255 } else { 0 };
+            Rgba([c, c, c, 255])
+        });
+        let texture =
+            ImageTexture::new(DynamicImage::ImageRgba8(image)).with_filter(Filter::Trilinear);
+
+        // one texel across, on a texel center once scaled up
+        let c = coord(2.5 / 64.0, 2.5 / 64.0).with_footprint(
+            Vector2::new([1.0 / 16.0, 0.0]),
+            Vector2::new([0.0, 1.0 / 16.0]),
+        );
+        let fine = texture.color(&TextureCoord {
+            uv: Point2D::new(2.5 / 16.0, 2.5 / 16.0),
+            ..c
+        });
+        assert!((fine.r - 1.0).abs() < 1e-6);
+
+        // four texels across picks a level where the checks average out
+        let node = Node::transform(texture, UvTransform::new().with_scale(4.0, 4.0));
+        assert!((node.color(&c).r - 0.5).abs() < 1e-6);
+    }
+}
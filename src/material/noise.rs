@@ -0,0 +1,303 @@
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{color::Color, math::Point3D};
+
+use super::{Texture, TextureCoord};
+
+/// The shape of a [Noise] texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// Plain gradient noise.
+    Perlin,
+    /// Several octaves of gradient noise, each finer and fainter than the last.
+    Fbm,
+    /// Like [Pattern::Fbm] but summing the absolute value of each octave,
+    /// which gives billowy, creased features.
+    Turbulence,
+    /// Veins of turbulence running along the x axis.
+    Marble,
+    /// Concentric rings around the y axis, perturbed by noise.
+    Wood,
+    /// Worley cells: the distance to the nearest of a set of scattered points.
+    Voronoi,
+}
+
+/// A solid texture evaluated at the object space hit point rather than at UV
+/// coordinates, so it works on shapes without a texture mapping.
+///
+/// Example:
+/// ```
+/// use raytracer_rs::color::Color;
+/// use raytracer_rs::material::{Material, Noise, Pattern};
+///
+/// let marble = Material::new(
+///     Noise::new(Pattern::Marble)
+///         .with_scale(4.0)
+///         .with_colors(Color::WHITE, Color::new(0.2, 0.2, 0.3)),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Noise {
+    pattern: Pattern,
+    scale: f64,
+    octaves: u32,
+    primary: Color,
+    secondary: Color,
+    perm: Vec<usize>,
+}
+
+impl Noise {
+    pub fn new(pattern: Pattern) -> Self {
+        Noise {
+            pattern,
+            scale: 1.0,
+            octaves: 6,
+            primary: Color::WHITE,
+            secondary: Color::BLACK,
+            perm: permutation(0),
+        }
+    }
+
+    /// Pick a different random variation of the same pattern.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.perm = permutation(seed);
+        self
+    }
+
+    /// The frequency of the pattern: larger values give smaller features.
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// The number of layers summed by the fractal patterns.
+    pub fn with_octaves(mut self, octaves: u32) -> Self {
+        self.octaves = octaves.max(1);
+        self
+    }
+
+    /// The colors at the high and low ends of the pattern.
+    pub fn with_colors(mut self, primary: Color, secondary: Color) -> Self {
+        self.primary = primary;
+        self.secondary = secondary;
+        self
+    }
+
+    pub fn color(&self, coord: &TextureCoord) -> Color {
+        self.secondary
+            .mix(self.primary, self.value_at(&coord.position))
+    }
+
+    /// The value of the pattern at `point`, in [0, 1].
+    pub fn value_at(&self, point: &Point3D) -> f64 {
+        let p = [
+            point.x() * self.scale,
+            point.y() * self.scale,
+            point.z() * self.scale,
+        ];
+
+        let value = match self.pattern {
+            Pattern::Perlin => 0.5 + 0.5 * self.perlin(p),
+            Pattern::Fbm => 0.5 + 0.5 * self.fractal(p, |n| n),
+            Pattern::Turbulence => self.fractal(p, f64::abs),
+            Pattern::Marble => {
+                let turbulence = self.fractal(p, f64::abs);
+                0.5 + 0.5 * (p[0] + 5.0 * turbulence).sin()
+            }
+            Pattern::Wood => {
+                let rings = (p[0] * p[0] + p[2] * p[2]).sqrt() + 0.5 * self.perlin(p);
+                rings.rem_euclid(1.0)
+            }
+            Pattern::Voronoi => self.worley(p),
+        };
+
+        value.clamp(0.0, 1.0)
+    }
+
+    fn hash(&self, x: i64, y: i64, z: i64) -> usize {
+        let (x, y, z) = ((x & 255) as usize, (y & 255) as usize, (z & 255) as usize);
+        self.perm[self.perm[self.perm[x] + y] + z]
+    }
+
+    /// Improved Perlin noise, in roughly [-1, 1].
+    fn perlin(&self, p: [f64; 3]) -> f64 {
+        let cell = [p[0].floor(), p[1].floor(), p[2].floor()];
+        let f = [p[0] - cell[0], p[1] - cell[1], p[2] - cell[2]];
+        let (x, y, z) = (cell[0] as i64, cell[1] as i64, cell[2] as i64);
+
+        let corner = |dx: i64, dy: i64, dz: i64| {
+            let h = self.hash(x + dx, y + dy, z + dz);
+            gradient(h, f[0] - dx as f64, f[1] - dy as f64, f[2] - dz as f64)
+        };
+
+        let (u, v, w) = (fade(f[0]), fade(f[1]), fade(f[2]));
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+            ),
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+            ),
+        )
+    }
+
+    /// Sum octaves of noise, passing each through `shape`.
+    fn fractal<F>(&self, p: [f64; 3], shape: F) -> f64
+    where
+        F: Fn(f64) -> f64,
+    {
+        let mut total = 0.0;
+        let mut norm = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+
+        for _ in 0..self.octaves {
+            let q = [p[0] * frequency, p[1] * frequency, p[2] * frequency];
+            total += amplitude * shape(self.perlin(q));
+            norm += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+
+        total / norm
+    }
+
+    /// Distance to the closest feature point, one per unit cell.
+    fn worley(&self, p: [f64; 3]) -> f64 {
+        let cell = [p[0].floor(), p[1].floor(), p[2].floor()];
+        let mut closest = f64::INFINITY;
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let (x, y, z) = (
+                        cell[0] as i64 + dx,
+                        cell[1] as i64 + dy,
+                        cell[2] as i64 + dz,
+                    );
+                    let h = self.hash(x, y, z);
+                    let feature = [
+                        x as f64 + self.perm[h] as f64 / 256.0,
+                        y as f64 + self.perm[h + 1] as f64 / 256.0,
+                        z as f64 + self.perm[h + 2] as f64 / 256.0,
+                    ];
+
+                    let d = (0..3)
+                        .map(|i| (feature[i] - p[i]).powi(2))
+                        .sum::<f64>()
+                        .sqrt();
+                    closest = closest.min(d);
+                }
+            }
+        }
+
+        closest
+    }
+}
+
+impl From<Noise> for Texture {
+    fn from(n: Noise) -> Self {
+        Texture::Noise(n)
+    }
+}
+
+/// A shuffled permutation of 0..256, repeated so lookups never need wrapping.
+///
+/// ChaCha rather than `StdRng`, whose output may change between releases of
+/// `rand`, so a seed gives the same pattern from one build to the next.
+fn permutation(seed: u64) -> Vec<usize> {
+    let mut perm = (0..256).collect::<Vec<usize>>();
+    perm.shuffle(&mut ChaCha8Rng::seed_from_u64(seed));
+    perm.extend_from_within(..);
+    perm
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+/// The dot product of the offset with one of twelve gradient directions.
+fn gradient(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATTERNS: [Pattern; 6] = [
+        Pattern::Perlin,
+        Pattern::Fbm,
+        Pattern::Turbulence,
+        Pattern::Marble,
+        Pattern::Wood,
+        Pattern::Voronoi,
+    ];
+
+    #[test]
+    fn perlin_vanishes_on_lattice_points() {
+        let noise = Noise::new(Pattern::Perlin);
+        for point in [[0.0, 0.0, 0.0], [1.0, 2.0, 3.0], [-4.0, 7.0, -1.0]].iter() {
+            assert!(noise.perlin(*point).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn values_stay_in_range() {
+        for pattern in PATTERNS.iter() {
+            let noise = Noise::new(*pattern).with_scale(3.7);
+            for i in 0..200 {
+                let t = i as f64 * 0.173;
+                let v = noise.value_at(&Point3D::new(t, -t * 0.5, t.sin()));
+                assert!((0.0..=1.0).contains(&v), "{:?} gave {}", pattern, v);
+            }
+        }
+    }
+
+    #[test]
+    fn seeds_change_the_pattern() {
+        let p = Point3D::new(0.3, 1.7, -2.2);
+        let a = Noise::new(Pattern::Fbm).with_seed(1);
+        let b = Noise::new(Pattern::Fbm).with_seed(2);
+
+        assert_eq!(a.value_at(&p), a.clone().value_at(&p));
+        assert_ne!(a.value_at(&p), b.value_at(&p));
+    }
+
+    #[test]
+    fn seeds_give_the_same_pattern_every_build() {
+        assert_eq!(permutation(0)[..8], [2, 29, 44, 147, 73, 65, 218, 193]);
+    }
+
+    #[test]
+    fn wood_rings_wrap_below_zero() {
+        let noise = Noise::new(Pattern::Wood);
+        let p = [0.03_f64, 0.1, -0.02];
+        let rings = (p[0] * p[0] + p[2] * p[2]).sqrt() + 0.5 * noise.perlin(p);
+        assert!(rings < 0.0);
+
+        let value = noise.value_at(&Point3D::new(p[0], p[1], p[2]));
+        assert!((value - (1.0 + rings)).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::math::{Point2D, Vector3};
+
+use super::{Texture, TextureCoord};
+
+/// The step (in texture space) used to estimate the slope of a height map.
+const BUMP_DELTA: f64 = 1e-3;
+
+/// Surface detail added by perturbing the shading normal rather than the
+/// geometry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NormalMap {
+    /// A tangent-space normal map, with X, Y and Z stored in the red, green
+    /// and blue channels remapped to [0, 1].
+    Normal(Texture),
+    /// A height map, read from the red channel. `strength` scales how far the
+    /// normal tilts for a given slope.
+    Bump { height: Texture, strength: f64 },
+}
+
+impl NormalMap {
+    pub fn bump(height: impl Into<Texture>, strength: f64) -> Self {
+        NormalMap::Bump {
+            height: height.into(),
+            strength,
+        }
+    }
+
+    /// The same map with its images read as data, not sRGB color.
+    pub(crate) fn into_linear(self) -> Self {
+        match self {
+            NormalMap::Normal(texture) => NormalMap::Normal(texture.into_linear()),
+            NormalMap::Bump { height, strength } => NormalMap::Bump {
+                height: height.into_linear(),
+                strength,
+            },
+        }
+    }
+
+    /// Perturb `normal` given the direction of increasing `u` across the
+    /// surface. Both vectors are expected to be in world space.
+    pub fn perturb(&self, normal: &Vector3, tangent: &Vector3, coord: &TextureCoord) -> Vector3 {
+        let normal = normal.normalize();
+        // make sure the frame is orthonormal, interpolation can skew it
+        let tangent = (tangent - normal * normal.dot(tangent)).normalize();
+        if tangent.magnitude().is_nan() {
+            return normal;
+        }
+
+        let bitangent = normal.cross(tangent);
+
+        match self {
+            NormalMap::Normal(texture) => {
+                let c = texture.color(coord);
+                let (x, y, z) = (2.0 * c.r - 1.0, 2.0 * c.g - 1.0, 2.0 * c.b - 1.0);
+                (tangent * x + bitangent * y + normal * z).normalize()
+            }
+            NormalMap::Bump { height, strength } => {
+                let h = height.value(coord);
+                // step along both the uv mapping and the surface itself, so
+                // solid textures make bumps too
+                let shifted = |du: f64, dv: f64, along: Vector3| {
+                    let uv = Point2D::new(coord.uv.x() + du, coord.uv.y() + dv);
+                    let position = coord.position + along * BUMP_DELTA;
+                    height.value(&TextureCoord {
+                        uv,
+                        position,
+                        ..*coord
+                    })
+                };
+
+                let dh_du = (shifted(BUMP_DELTA, 0.0, tangent) - h) / BUMP_DELTA;
+                let dh_dv = (shifted(0.0, BUMP_DELTA, bitangent) - h) / BUMP_DELTA;
+
+                (normal - (tangent * dh_du + bitangent * dh_dv) * *strength).normalize()
+            }
+        }
+    }
+}
+
+impl From<Texture> for NormalMap {
+    fn from(t: Texture) -> Self {
+        NormalMap::Normal(t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn coord() -> TextureCoord {
+        TextureCoord::new(Point2D::new(0.3, 0.7), 1.0)
+    }
+
+    #[test]
+    fn flat_maps_leave_the_normal_alone() {
+        let flat = NormalMap::Normal(Color::new(0.5, 0.5, 1.0).into());
+        assert_eq!(flat.perturb(&Vector3::J, &Vector3::I, &coord()), Vector3::J);
+
+        let level = NormalMap::bump(0.5, 10.0);
+        assert_eq!(
+            level.perturb(&Vector3::J, &Vector3::I, &coord()),
+            Vector3::J
+        );
+    }
+
+    #[test]
+    fn normal_maps_tilt_towards_the_tangent() {
+        let tilted = NormalMap::Normal(Color::new(1.0, 0.5, 0.5).into());
+        let n = tilted.perturb(&Vector3::K, &Vector3::I, &coord());
+        assert!((n - Vector3::I).magnitude() < 1e-9);
+    }
+}
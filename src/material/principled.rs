@@ -0,0 +1,241 @@
+use std::f64::consts::PI;
+
+use crate::{color::Color, math::Vector3};
+
+use super::{Texture, TextureCoord};
+
+/// A "principled" material in the spirit of the Disney BRDF (and Blender's
+/// Principled BSDF). Every parameter is a [Texture](crate::material::Texture);
+/// scalar parameters are read from the red channel of their texture, so a
+/// plain `f64` can be supplied wherever a constant is wanted.
+///
+/// Example:
+/// ```
+/// use raytracer_rs::color::Color;
+/// use raytracer_rs::material::{Material, Principled};
+///
+/// let gold = Material::new(Color::WHITE).with_principled(
+///     Principled::new(Color::new(1.0, 0.76, 0.33))
+///         .with_metallic(1.0)
+///         .with_roughness(0.3),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Principled {
+    pub base_color: Texture,
+    pub metallic: Texture,
+    pub roughness: Texture,
+    pub specular: Texture,
+    pub sheen: Texture,
+    pub clearcoat: Texture,
+    pub transmission: Texture,
+    pub ior: f64,
+}
+
+/// The parameters of a [Principled] material resolved at a single texture
+/// coordinate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrincipledSample {
+    pub base_color: Color,
+    pub metallic: f64,
+    pub roughness: f64,
+    pub specular: f64,
+    pub sheen: f64,
+    pub clearcoat: f64,
+    pub transmission: f64,
+}
+
+impl Principled {
+    pub fn new(base_color: impl Into<Texture>) -> Self {
+        Principled {
+            base_color: base_color.into(),
+            metallic: 0.0.into(),
+            roughness: 0.5.into(),
+            specular: 0.5.into(),
+            sheen: 0.0.into(),
+            clearcoat: 0.0.into(),
+            transmission: 0.0.into(),
+            ior: 1.45,
+        }
+    }
+
+    pub fn with_metallic(mut self, metallic: impl Into<Texture>) -> Self {
+        self.metallic = metallic.into();
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: impl Into<Texture>) -> Self {
+        self.roughness = roughness.into();
+        self
+    }
+
+    pub fn with_specular(mut self, specular: impl Into<Texture>) -> Self {
+        self.specular = specular.into();
+        self
+    }
+
+    pub fn with_sheen(mut self, sheen: impl Into<Texture>) -> Self {
+        self.sheen = sheen.into();
+        self
+    }
+
+    pub fn with_clearcoat(mut self, clearcoat: impl Into<Texture>) -> Self {
+        self.clearcoat = clearcoat.into();
+        self
+    }
+
+    pub fn with_transmission(mut self, transmission: impl Into<Texture>) -> Self {
+        self.transmission = transmission.into();
+        self
+    }
+
+    pub fn with_ior(mut self, ior: f64) -> Self {
+        self.ior = ior;
+        self
+    }
+
+    pub fn sample(&self, coord: &TextureCoord) -> PrincipledSample {
+        PrincipledSample {
+            base_color: self.base_color.color(coord),
+            metallic: self.metallic.value(coord).clamp(0.0, 1.0),
+            // very small roughness values make the GGX lobe numerically
+            // unstable, so keep a floor under it
+            roughness: self.roughness.value(coord).clamp(0.02, 1.0),
+            specular: self.specular.value(coord).max(0.0),
+            sheen: self.sheen.value(coord).max(0.0),
+            clearcoat: self.clearcoat.value(coord).clamp(0.0, 1.0),
+            transmission: self.transmission.value(coord).clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Principled::new(Color::new(0.8, 0.8, 0.8))
+    }
+}
+
+impl PrincipledSample {
+    /// The color of normal incidence specular reflection.
+    pub fn specular_tint(&self) -> Color {
+        let dielectric = Color::WHITE * (0.08 * self.specular);
+        dielectric.mix(self.base_color, self.metallic)
+    }
+
+    /// The share of light scattered diffusely, which is what ambient light
+    /// lights.
+    pub fn diffuse_albedo(&self) -> Color {
+        self.base_color * ((1.0 - self.metallic) * (1.0 - self.transmission))
+    }
+
+    /// Evaluate the BRDF for light arriving from `light_dir` and leaving
+    /// towards `view_dir`. Both directions point away from the surface.
+    pub fn eval(&self, normal: &Vector3, view_dir: &Vector3, light_dir: &Vector3) -> Color {
+        let n_dot_l = normal.dot(light_dir);
+        let n_dot_v = normal.dot(view_dir);
+
+        if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+            return Color::BLACK;
+        }
+
+        let half = (light_dir + view_dir).normalize();
+        let n_dot_h = normal.dot(half);
+        let l_dot_h = light_dir.dot(half);
+
+        let fl = schlick_weight(n_dot_l);
+        let fv = schlick_weight(n_dot_v);
+        let fh = schlick_weight(l_dot_h);
+
+        // Burley diffuse with retro-reflection at grazing angles
+        let fd90 = 0.5 + 2.0 * l_dot_h * l_dot_h * self.roughness;
+        let fd = (1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv);
+        let diffuse = self.base_color * (fd / PI);
+        let sheen = Color::WHITE * (fh * self.sheen);
+
+        // GGX specular
+        let alpha = self.roughness * self.roughness;
+        let ds = gtr2(n_dot_h, alpha);
+        let fs = self.specular_tint().mix(Color::WHITE, fh);
+        let gs = smith_ggx(n_dot_l, alpha) * smith_ggx(n_dot_v, alpha);
+        let specular = fs * (ds * gs);
+
+        // clear coat is a fixed, fairly glossy lobe with an IOR of 1.5
+        let dr = gtr1(n_dot_h, CLEARCOAT_ALPHA);
+        let fr = 0.04 + 0.96 * fh;
+        let gr = smith_ggx(n_dot_l, 0.25) * smith_ggx(n_dot_v, 0.25);
+        let clearcoat = 0.25 * self.clearcoat * dr * fr * gr;
+
+        (diffuse + sheen) * ((1.0 - self.metallic) * (1.0 - self.transmission))
+            + specular
+            + Color::WHITE * clearcoat
+    }
+
+    /// The fraction of light reflected in the mirror direction, used to weight
+    /// the reflection ray traced for smooth and coated surfaces.
+    pub fn reflectance(&self, normal: &Vector3, view_dir: &Vector3) -> Color {
+        let fv = schlick_weight(normal.dot(view_dir).max(0.0));
+        let gloss = (1.0 - self.roughness).powi(2);
+        let specular = self.specular_tint().mix(Color::WHITE, fv) * gloss;
+        let clearcoat = self.clearcoat * 0.25 * (0.04 + 0.96 * fv);
+
+        specular + Color::WHITE * clearcoat
+    }
+}
+
+const CLEARCOAT_ALPHA: f64 = 0.05;
+
+fn schlick_weight(cos: f64) -> f64 {
+    (1.0 - cos).clamp(0.0, 1.0).powi(5)
+}
+
+fn gtr1(n_dot_h: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    let t = 1.0 + (a2 - 1.0) * n_dot_h * n_dot_h;
+    (a2 - 1.0) / (PI * a2.ln() * t)
+}
+
+fn gtr2(n_dot_h: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    let t = 1.0 + (a2 - 1.0) * n_dot_h * n_dot_h;
+    a2 / (PI * t * t)
+}
+
+fn smith_ggx(n_dot_v: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    let b = n_dot_v * n_dot_v;
+    1.0 / (n_dot_v + (a2 + b - a2 * b).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Point2D;
+
+    fn coord() -> TextureCoord {
+        TextureCoord::new(Point2D::default(), 1.0)
+    }
+
+    #[test]
+    fn metals_tint_specular_with_base_color() {
+        let params = Principled::new(Color::RED)
+            .with_metallic(1.0)
+            .sample(&coord());
+        assert_eq!(params.specular_tint(), Color::RED);
+    }
+
+    #[test]
+    fn no_light_below_the_horizon() {
+        let params = Principled::default().sample(&coord());
+        let below = Vector3::new([0.0, -1.0, 1.0]).normalize();
+        assert_eq!(params.eval(&Vector3::J, &Vector3::J, &below), Color::BLACK);
+        assert_eq!(params.eval(&Vector3::J, &below, &Vector3::J), Color::BLACK);
+    }
+
+    #[test]
+    fn scalar_parameters_read_textures() {
+        let params = Principled::default()
+            .with_roughness(Color::new(0.25, 0.9, 0.9))
+            .sample(&coord());
+        assert_eq!(params.roughness, 0.25);
+    }
+}
@@ -1,14 +1,17 @@
-use image::{DynamicImage, GenericImageView};
+use crate::{
+    color::Color,
+    math::{Point2D, Point3D, Vector2, Vector3},
+};
 
-use crate::{color::Color, math::Point2D};
-
-use super::Checker;
+use super::{Checker, ImageTexture, Node, Noise};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Texture {
     Color(Color),
     Checker(Checker),
-    Image(DynamicImage),
+    Image(ImageTexture),
+    Noise(Noise),
+    Node(Box<Node>),
 }
 
 impl Texture {
@@ -16,17 +19,74 @@ impl Texture {
         match self {
             Texture::Color(color) => color.clone(),
             Texture::Checker(checker) => checker.color(coord),
-            Texture::Image(image) => {
-                let (x, y) = coord.scale_wrap(coord.scale, image.width(), image.height());
-                image.get_pixel(x, y).into()
-            }
+            Texture::Image(image) => image.color(coord),
+            Texture::Noise(noise) => noise.color(coord),
+            Texture::Node(node) => node.color(coord),
         }
     }
+
+    /// The scalar value of this texture at `coord`, read from the red channel.
+    pub fn value(&self, coord: &TextureCoord) -> f64 {
+        self.color(coord).r
+    }
+
+    /// The same texture with any images read as data rather than color.
+    pub(crate) fn into_linear(self) -> Self {
+        match self {
+            Texture::Image(image) => Texture::Image(image.into_linear()),
+            Texture::Node(node) => Texture::Node(Box::new(node.into_linear())),
+            other => other,
+        }
+    }
+}
+
+impl From<f64> for Texture {
+    fn from(v: f64) -> Self {
+        Texture::Color(Color::new(v, v, v))
+    }
+}
+
+/// What a texture shows outside of the [0, 1] range of texture coordinates.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    /// Repeat, flipping every other tile so edges line up.
+    MirroredRepeat,
+    /// Stretch the outermost texels.
+    ClampToEdge,
+    /// Show a solid color, for decals and labels that shouldn't tile.
+    ClampToBorder(Color),
 }
 
-impl From<DynamicImage> for Texture {
-    fn from(i: DynamicImage) -> Self {
-        Texture::Image(i)
+impl WrapMode {
+    /// Map texel index `i` into a row or column `size` texels long, or `None`
+    /// if it falls on the border.
+    pub fn texel(&self, i: i64, size: usize) -> Option<usize> {
+        let n = size as i64;
+        let wrapped = match self {
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::MirroredRepeat => {
+                let m = i.rem_euclid(2 * n);
+                if m < n {
+                    m
+                } else {
+                    2 * n - 1 - m
+                }
+            }
+            WrapMode::ClampToEdge => i.clamp(0, n - 1),
+            WrapMode::ClampToBorder(_) if i < 0 || i >= n => return None,
+            WrapMode::ClampToBorder(_) => i,
+        };
+
+        Some(wrapped as usize)
+    }
+
+    pub fn border(&self) -> Color {
+        match self {
+            WrapMode::ClampToBorder(color) => *color,
+            _ => Color::BLACK,
+        }
     }
 }
 
@@ -34,11 +94,42 @@ impl From<DynamicImage> for Texture {
 pub struct TextureCoord {
     pub uv: Point2D,
     pub scale: f64,
+    /// The hit point in object space, used by solid textures.
+    pub position: Point3D,
+    /// The surface normal in object space, used by triplanar projection.
+    pub normal: Vector3,
+    /// How far the texture coordinates move across one pixel in x and y,
+    /// used to filter image textures. Zero when unknown.
+    pub duv_dx: Vector2,
+    pub duv_dy: Vector2,
 }
 
 impl TextureCoord {
     pub fn new(uv: Point2D, scale: f64) -> Self {
-        TextureCoord { uv, scale }
+        TextureCoord {
+            uv,
+            scale,
+            position: Point3D::default(),
+            normal: Vector3::ZERO,
+            duv_dx: Vector2::ZERO,
+            duv_dy: Vector2::ZERO,
+        }
+    }
+
+    pub fn with_position(mut self, position: Point3D) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_normal(mut self, normal: Vector3) -> Self {
+        self.normal = normal;
+        self
+    }
+
+    pub fn with_footprint(mut self, duv_dx: Vector2, duv_dy: Vector2) -> Self {
+        self.duv_dx = duv_dx;
+        self.duv_dy = duv_dy;
+        self
     }
 
     pub fn scale_wrap(&self, scale: f64, max_x: u32, max_y: u32) -> (u32, u32) {
@@ -0,0 +1,141 @@
+use std::f64::consts::PI;
+
+use super::{Texture, TextureCoord};
+
+/// What sits underneath a [ThinFilm].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Substrate {
+    /// A dielectric with the given refractive index.
+    Dielectric(f64),
+    /// A metal reflecting the given fraction of light.
+    Conductor(f64),
+}
+
+/// A thin transparent coating (soap, oil, lens coatings) whose interference
+/// produces view-dependent iridescent color.
+///
+/// Example:
+/// ```
+/// use raytracer_rs::color::Color;
+/// use raytracer_rs::material::{Material, Surface, ThinFilm};
+///
+/// let bubble = Material::new(Color::WHITE)
+///     .with_surface(Surface::Refractive { index: 1.0, transparency: 1.0 })
+///     .with_thin_film(ThinFilm::new(400.0, 1.33));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThinFilm {
+    pub thickness: f64,
+    pub thickness_map: Option<Texture>,
+    pub ior: f64,
+}
+
+impl ThinFilm {
+    /// A film `thickness` nanometers thick with refractive index `ior`.
+    pub fn new(thickness: f64, ior: f64) -> Self {
+        ThinFilm {
+            thickness,
+            thickness_map: None,
+            ior,
+        }
+    }
+
+    /// Vary the thickness over the surface: the red channel of `map` scales
+    /// the base thickness.
+    pub fn with_thickness_map(mut self, map: impl Into<Texture>) -> Self {
+        self.thickness_map = Some(map.into());
+        self
+    }
+
+    pub fn thickness_at(&self, coord: &TextureCoord) -> f64 {
+        match &self.thickness_map {
+            Some(map) => self.thickness * map.value(coord).max(0.0),
+            None => self.thickness,
+        }
+    }
+
+    /// The fraction of light of `wavelength` (in nm) reflected by the film on
+    /// top of `substrate`, for light arriving at `cos_i` to the normal.
+    pub fn reflectance(
+        &self,
+        coord: &TextureCoord,
+        cos_i: f64,
+        substrate: Substrate,
+        wavelength: f64,
+    ) -> f64 {
+        let cos1 = cos_i.clamp(0.0, 1.0);
+        let sin1_2 = 1.0 - cos1 * cos1;
+
+        let n2 = self.ior;
+        let cos2_2 = 1.0 - sin1_2 / (n2 * n2);
+        if cos2_2 <= 0.0 {
+            return 1.0;
+        }
+        let cos2 = cos2_2.sqrt();
+
+        // amplitude coefficients at the top of the film for each polarization
+        let r12 = [
+            (cos1 - n2 * cos2) / (cos1 + n2 * cos2),
+            (n2 * cos1 - cos2) / (n2 * cos1 + cos2),
+        ];
+
+        // and at the bottom
+        let r23 = match substrate {
+            Substrate::Dielectric(n3) => {
+                let cos3_2 = 1.0 - sin1_2 / (n3 * n3);
+                if cos3_2 <= 0.0 {
+                    [1.0, 1.0]
+                } else {
+                    let cos3 = cos3_2.sqrt();
+                    [
+                        (n2 * cos2 - n3 * cos3) / (n2 * cos2 + n3 * cos3),
+                        (n3 * cos2 - n2 * cos3) / (n3 * cos2 + n2 * cos3),
+                    ]
+                }
+            }
+            // metals flip the phase of what they reflect
+            Substrate::Conductor(reflectivity) => {
+                let r = -reflectivity.clamp(0.0, 1.0).sqrt();
+                [r, r]
+            }
+        };
+
+        let delta = 4.0 * PI * n2 * self.thickness_at(coord) * cos2 / wavelength;
+        let cos_delta = delta.cos();
+
+        let airy = |a: f64, b: f64| {
+            (a * a + b * b + 2.0 * a * b * cos_delta)
+                / (1.0 + a * a * b * b + 2.0 * a * b * cos_delta)
+        };
+
+        ((airy(r12[0], r23[0]) + airy(r12[1], r23[1])) / 2.0).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Point2D;
+
+    fn coord() -> TextureCoord {
+        TextureCoord::new(Point2D::default(), 1.0)
+    }
+
+    #[test]
+    fn vanishing_films_match_plain_fresnel() {
+        // a film with no thickness leaves the bare air/glass interface
+        let film = ThinFilm::new(0.0, 1.33);
+        let r = film.reflectance(&coord(), 1.0, Substrate::Dielectric(1.5), 550.0);
+        let expected = ((1.0 - 1.5) / (1.0 + 1.5) as f64).powi(2);
+        assert!((r - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn quarter_wave_coatings_are_anti_reflective() {
+        // the classic lens coating: n = sqrt(n_glass), a quarter wave thick
+        let n = 1.5_f64.sqrt();
+        let film = ThinFilm::new(550.0 / (4.0 * n), n);
+        let r = film.reflectance(&coord(), 1.0, Substrate::Dielectric(1.5), 550.0);
+        assert!(r < 1e-9);
+    }
+}
@@ -1,10 +1,59 @@
 use crate::math::{Point3D, Vector3};
 
+/// Two auxiliary rays offset by one pixel in x and y from the ray they belong
+/// to, used to estimate how much of a surface a pixel covers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayDifferential {
+    pub x_origin: Point3D,
+    pub x_direction: Vector3,
+    pub y_origin: Point3D,
+    pub y_direction: Vector3,
+}
+
+impl RayDifferential {
+    /// Where the auxiliary rays cross the plane through `point` with `normal`,
+    /// relative to `point`. Yields `None` if they run parallel to the plane.
+    pub fn offsets(&self, point: &Point3D, normal: &Vector3) -> Option<(Vector3, Vector3)> {
+        let hit = |origin: &Point3D, direction: &Vector3| {
+            let denom = direction.dot(normal);
+            if denom.abs() < 1e-12 {
+                return None;
+            }
+
+            let t = (point - origin).dot(normal) / denom;
+            Some(origin + direction * t - point)
+        };
+
+        Some((
+            hit(&self.x_origin, &self.x_direction)?,
+            hit(&self.y_origin, &self.y_direction)?,
+        ))
+    }
+
+    /// Follow the auxiliary rays through a bounce at `point`, bending their
+    /// directions with `bend`.
+    fn transfer<F>(&self, point: &Point3D, normal: &Vector3, bend: F) -> Option<Self>
+    where
+        F: Fn(&Vector3) -> Option<Vector3>,
+    {
+        let (dx, dy) = self.offsets(point, normal)?;
+
+        Some(RayDifferential {
+            x_origin: point + dx,
+            x_direction: bend(&self.x_direction)?,
+            y_origin: point + dy,
+            y_direction: bend(&self.y_direction)?,
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Ray {
     origin: Point3D,
     direction: Vector3,
     generation: usize,
+    wavelength: Option<f64>,
+    differential: Option<RayDifferential>,
 }
 
 impl Ray {
@@ -17,9 +66,23 @@ impl Ray {
             origin,
             direction,
             generation,
+            wavelength: None,
+            differential: None,
         }
     }
 
+    /// Tag this ray with a single wavelength (in nm). Rays spawned from it
+    /// carry the same wavelength.
+    pub fn with_wavelength(mut self, wavelength: f64) -> Self {
+        self.wavelength = Some(wavelength);
+        self
+    }
+
+    pub fn with_differential(mut self, differential: RayDifferential) -> Self {
+        self.differential = Some(differential);
+        self
+    }
+
     pub fn origin(&self) -> &Point3D {
         &self.origin
     }
@@ -28,16 +91,32 @@ impl Ray {
         &self.direction
     }
 
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
+    }
+
+    pub fn differential(&self) -> Option<&RayDifferential> {
+        self.differential.as_ref()
+    }
+
     pub fn point_at(&self, distance: f64) -> Point3D {
         distance * self.direction + self.origin
     }
 
     pub fn reflect(&self, normal: &Vector3, intersection: &Point3D, offset: f64) -> Self {
-        Self::with_generation(
-            intersection + normal * offset,
-            (self.direction - (2.0 * self.direction.dot(normal) * normal)).normalize(),
-            self.generation + 1,
-        )
+        let reflect = |d: &Vector3| (d - (2.0 * d.dot(normal) * normal)).normalize();
+
+        Self {
+            wavelength: self.wavelength,
+            differential: self
+                .differential
+                .and_then(|d| d.transfer(intersection, normal, |v| Some(reflect(v)))),
+            ..Self::with_generation(
+                intersection + normal * offset,
+                reflect(&self.direction),
+                self.generation + 1,
+            )
+        }
     }
 
     pub fn refract(
@@ -47,30 +126,22 @@ impl Ray {
         offset: f64,
         refractive_index: f64,
     ) -> Option<Self> {
-        let mut n = normal.clone();
-        let mut eta_i = 1.0;
-        let mut eta_t = refractive_index;
-        let mut i_dot_n = self.direction.dot(n);
-        if i_dot_n < 0.0 {
-            i_dot_n = -i_dot_n;
+        let direction = refract_direction(&self.direction, normal, refractive_index)?;
+        let n = if self.direction.dot(normal) < 0.0 {
+            *normal
         } else {
-            n = -n;
-            eta_t = 1.0;
-            eta_i = refractive_index;
-        }
+            -normal
+        };
 
-        let eta = eta_i / eta_t;
-        let k = 1.0 - (eta * eta) * (1.0 - i_dot_n * i_dot_n);
-
-        if k < 0.0 {
-            None
-        } else {
-            Some(Self::with_generation(
-                intersection + n * -offset,
-                (self.direction() + i_dot_n * n) * eta - n * k.sqrt(),
-                self.generation + 1,
-            ))
-        }
+        Some(Self {
+            wavelength: self.wavelength,
+            differential: self.differential.and_then(|d| {
+                d.transfer(intersection, normal, |v| {
+                    refract_direction(v, normal, refractive_index)
+                })
+            }),
+            ..Self::with_generation(intersection + n * -offset, direction, self.generation + 1)
+        })
     }
 
     pub fn generation(&self) -> usize {
@@ -81,3 +152,30 @@ impl Ray {
         self.generation() == 0
     }
 }
+
+fn refract_direction(
+    direction: &Vector3,
+    normal: &Vector3,
+    refractive_index: f64,
+) -> Option<Vector3> {
+    let mut n = normal.clone();
+    let mut eta_i = 1.0;
+    let mut eta_t = refractive_index;
+    let mut i_dot_n = direction.dot(n);
+    if i_dot_n < 0.0 {
+        i_dot_n = -i_dot_n;
+    } else {
+        n = -n;
+        eta_t = 1.0;
+        eta_i = refractive_index;
+    }
+
+    let eta = eta_i / eta_t;
+    let k = 1.0 - (eta * eta) * (1.0 - i_dot_n * i_dot_n);
+
+    if k < 0.0 {
+        None
+    } else {
+        Some((direction + i_dot_n * n) * eta - n * k.sqrt())
+    }
+}
@@ -4,16 +4,18 @@ use image::{imageops, DynamicImage, GenericImage};
 #[cfg(feature = "feedback")]
 use indicatif::{ParallelProgressIterator, ProgressBar};
 use itertools::Itertools;
+use rand::Rng;
 use rayon::prelude::*;
 
 use crate::{
     camera::Camera,
     color::Color,
-    light::Light,
-    material::{Surface, TextureCoord, Textured},
-    math::{Point3D, Vector3},
-    ray::Ray,
-    shape::{Intersect, Intersection, Shape, Transformable},
+    light::{luminance, Light, LightSample},
+    material::{Material, Principled, Substrate, Surface, TextureCoord, Textured, ThinFilm},
+    math::{Point3D, Vector2, Vector3},
+    ray::{Ray, RayDifferential},
+    shape::{Intersect, Intersection, Sampleable, Shape, Transformable},
+    spectrum::{wavelength_samples, SpectralFilm},
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -41,13 +43,98 @@ impl Default for View {
     }
 }
 
+/// Refers to a shape added to a [Scene].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShapeId(usize);
+
+/// Refers to a light added to a [Scene].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LightId(usize);
+
+/// Which kinds of rays a shape takes part in. Everything is on by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Visibility {
+    /// Whether the shape blocks light from reaching other shapes.
+    pub casts_shadows: bool,
+    /// Whether other shapes can block light from reaching the shape.
+    pub receives_shadows: bool,
+    /// Whether camera rays can hit the shape.
+    pub camera: bool,
+    /// Whether reflected and refracted rays can hit the shape. There's no
+    /// separate switch for refraction, so turning this off also hides the
+    /// shape behind glass and water.
+    pub reflections: bool,
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Visibility {
+            casts_shadows: true,
+            receives_shadows: true,
+            camera: true,
+            reflections: true,
+        }
+    }
+}
+
+/// Which shapes a light illuminates. Emissive shapes aren't lights and
+/// can't be linked; they light every shape.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum LightLink {
+    #[default]
+    All,
+    Only(Vec<ShapeId>),
+    Except(Vec<ShapeId>),
+}
+
+impl LightLink {
+    pub fn includes(&self, shape: ShapeId) -> bool {
+        match self {
+            LightLink::All => true,
+            LightLink::Only(shapes) => shapes.contains(&shape),
+            LightLink::Except(shapes) => !shapes.contains(&shape),
+        }
+    }
+}
+
+/// The number of wavelengths used to work out the color of thin film
+/// interference for RGB rays.
+const THIN_FILM_SAMPLES: usize = 16;
+
+/// How many cut out holes in a single shape a ray may pass through before
+/// giving up on it.
+const MAX_CUTOUT_CROSSINGS: usize = 64;
+
+/// How many transparent surfaces a shadow ray may pass through before the
+/// light counts as blocked.
+const MAX_SHADOW_CROSSINGS: usize = 16;
+
+/// Something other than ambient light that lights a shading point.
+#[derive(Clone, Copy)]
+enum Source<'a> {
+    Light(&'a Light),
+    Emitter(&'a Shape),
+}
+
+/// Light arriving at a shading point from a single direction.
+struct Incident {
+    direction: Vector3,
+    radiance: Color,
+}
+
 pub struct Scene {
     cameras: Vec<Camera>,
     lights: Vec<Light>,
+    light_links: Vec<LightLink>,
     shapes: Vec<Shape>,
+    visibility: Vec<Visibility>,
     view: View,
     background: Color,
     max_generations: usize,
+    emission_samples: usize,
+    light_samples: usize,
+    dispersion_samples: usize,
+    spectral: Option<SpectralFilm>,
 }
 
 impl Scene {
@@ -55,10 +142,16 @@ impl Scene {
         Self {
             cameras: Vec::new(),
             lights: Vec::new(),
+            light_links: Vec::new(),
             shapes: Vec::new(),
+            visibility: Vec::new(),
             view: View::default(),
             background: Color::default(),
             max_generations: 5,
+            emission_samples: 8,
+            light_samples: 0,
+            dispersion_samples: 8,
+            spectral: None,
         }
     }
 
@@ -66,6 +159,38 @@ impl Scene {
         self.max_generations = max
     }
 
+    /// The number of shadow rays fired at each emissive shape per shading
+    /// point. More samples mean smoother soft shadows from glowing objects.
+    pub fn set_emission_samples(&mut self, samples: usize) {
+        self.emission_samples = samples
+    }
+
+    /// Pick `samples` lights or emissive shapes at random per shading point
+    /// instead of sampling all of them, favouring the ones likely to
+    /// contribute most, and trace one shadow ray to each. This keeps scenes
+    /// with hundreds of small lights fast, at the cost of noise. Zero samples
+    /// every light.
+    pub fn set_light_samples(&mut self, samples: usize) {
+        self.light_samples = samples
+    }
+
+    /// The number of wavelengths a ray is split into when it hits a
+    /// dispersive material.
+    pub fn set_dispersion_samples(&mut self, samples: usize) {
+        self.dispersion_samples = samples
+    }
+
+    /// Render spectrally, tracing `samples` wavelengths per pixel instead of
+    /// RGB. Colors are upsampled to spectra as they're encountered, unless a
+    /// light or emissive material was given its own spectrum, and the result
+    /// is converted back through CIE XYZ. Zero switches back to RGB.
+    pub fn set_spectral_samples(&mut self, samples: usize) {
+        self.spectral = match samples {
+            0 => None,
+            n => Some(SpectralFilm::new(n)),
+        };
+    }
+
     pub fn set_view(&mut self, view: View) {
         self.view = view;
     }
@@ -74,15 +199,51 @@ impl Scene {
         self.cameras.push(camera);
     }
 
-    pub fn add_shape<T>(&mut self, shape: T)
+    pub fn add_shape<T>(&mut self, shape: T) -> ShapeId
     where
         T: Intersect + Textured + Transformable + Into<Shape>,
     {
         self.shapes.push(shape.into());
+        self.visibility.push(Visibility::default());
+        ShapeId(self.shapes.len() - 1)
     }
 
-    pub fn add_light(&mut self, light: impl Into<Light>) {
+    pub fn add_light(&mut self, light: impl Into<Light>) -> LightId {
         self.lights.push(light.into());
+        self.light_links.push(LightLink::All);
+        LightId(self.lights.len() - 1)
+    }
+
+    pub fn set_visibility(&mut self, shape: ShapeId, visibility: Visibility) {
+        self.visibility[shape.0] = visibility;
+    }
+
+    /// Restrict the shapes `light` illuminates.
+    ///
+    /// Example:
+    /// ```
+    /// use raytracer_rs::light::PointLight;
+    /// use raytracer_rs::math::{Point3D, Vector3};
+    /// use raytracer_rs::scene::{LightLink, Visibility};
+    /// use raytracer_rs::shape::{Plane, Sphere};
+    /// use raytracer_rs::Scene;
+    ///
+    /// let mut scene = Scene::new();
+    /// let product = scene.add_shape(Sphere::new(Point3D::new(0.0, 1.0, 0.0), 1.0));
+    /// let floor = scene.add_shape(Plane::new(Point3D::default(), Vector3::J));
+    ///
+    /// let key = scene.add_light(PointLight::new(Point3D::new(2.0, 5.0, -3.0)));
+    /// scene.link_light(key, LightLink::Only(vec![product]));
+    /// scene.set_visibility(
+    ///     floor,
+    ///     Visibility {
+    ///         receives_shadows: false,
+    ///         ..Visibility::default()
+    ///     },
+    /// );
+    /// ```
+    pub fn link_light(&mut self, light: LightId, link: LightLink) {
+        self.light_links[light.0] = link;
     }
 
     pub fn raytrace(&self) -> Vec<DynamicImage> {
@@ -130,11 +291,9 @@ impl Scene {
             .cartesian_product(0..self.view.height)
             .into_iter()
             .for_each(|(x, y)| {
-                let (sx, sy) = self.view.to_plane_coord(x, y);
-                let v = (d * camera.forward() + sx * camera.right() + sy * camera.up()).normalize();
-                let ray = Ray::new(camera.origin().clone(), v);
+                let ray = self.camera_ray(camera, d, x, y);
 
-                let col = self.color_for(&ray);
+                let col = self.primary_color(&ray);
 
                 img.put_pixel(x, y, col.into());
             });
@@ -156,11 +315,9 @@ impl Scene {
         iter.map(|x| {
             let mut partial = DynamicImage::new_rgb8(1, self.view.height);
             for y in 0..self.view.height {
-                let (sx, sy) = self.view.to_plane_coord(x, y);
-                let v = (d * camera.forward() + sx * camera.right() + sy * camera.up()).normalize();
-                let ray = Ray::new(camera.origin().clone(), v);
+                let ray = self.camera_ray(camera, d, x, y);
 
-                let col = self.color_for(&ray);
+                let col = self.primary_color(&ray);
                 partial.put_pixel(0, y, col.into());
             }
             (partial, x)
@@ -188,11 +345,9 @@ impl Scene {
         let iter = iter.progress_with(pb);
 
         iter.map(|(x, y)| {
-            let (sx, sy) = self.view.to_plane_coord(x, y);
-            let v = (d * camera.forward() + sx * camera.right() + sy * camera.up()).normalize();
-            let ray = Ray::new(camera.origin().clone(), v);
+            let ray = self.camera_ray(camera, d, x, y);
 
-            let col = self.color_for(&ray);
+            let col = self.primary_color(&ray);
             (x, y, col)
         })
         .collect::<Vec<(u32, u32, Color)>>()
@@ -202,28 +357,388 @@ impl Scene {
         img
     }
 
+    /// The primary ray through pixel (`x`, `y`) for a camera whose image plane
+    /// sits `d` pixels away, along with differentials to its neighbours.
+    fn camera_ray(&self, camera: &Camera, d: f64, x: u32, y: u32) -> Ray {
+        let (sx, sy) = self.view.to_plane_coord(x, y);
+        let direction = |sx: f64, sy: f64| {
+            (d * camera.forward() + sx * camera.right() + sy * camera.up()).normalize()
+        };
+
+        let origin = *camera.origin();
+        Ray::new(origin, direction(sx, sy)).with_differential(RayDifferential {
+            x_origin: origin,
+            x_direction: direction(sx + 1.0, sy),
+            y_origin: origin,
+            y_direction: direction(sx, sy - 1.0),
+        })
+    }
+
+    /// How much of the light in `sample` reaches `point`: white when nothing
+    /// is in the way, black behind opaque objects, and tinted by the color
+    /// and absorption of any transparent objects it passes through.
+    fn shadow_transmittance(
+        &self,
+        sample: &LightSample,
+        point: &Point3D,
+        normal: &Vector3,
+        wavelength: Option<f64>,
+    ) -> Color {
+        let mut shadow = Ray::new(point + normal * 1e-10_f64, sample.direction);
+        let mut remaining = sample.distance;
+        let mut transmittance = Color::WHITE;
+
+        for _ in 0..MAX_SHADOW_CROSSINGS {
+            let hit = match self.get_closest_intersection(&shadow, |v| v.casts_shadows) {
+                Some(hit) if hit.distance < remaining => hit,
+                _ => return transmittance,
+            };
+
+            let hit_point = hit
+                .location
+                .unwrap_or_else(|| shadow.point_at(hit.distance));
+            let normal = hit.normal.or_else(|| hit.obj.normal_at(&hit_point));
+            let object_normal = hit
+                .obj
+                .transform_normal_to_object(normal.unwrap_or(Vector3::ZERO));
+            let coord = hit
+                .tex_coord
+                .unwrap_or_else(|| hit.obj.texture_coord(&hit_point))
+                .with_position(object_space(hit.obj, &hit_point))
+                .with_normal(object_normal);
+
+            let material = hit.obj.material();
+            let filter = match material.shadow_filter(&coord) {
+                Some(filter) => filter,
+                None => return Color::BLACK,
+            };
+            transmittance = transmittance * filter.at_wavelength(wavelength);
+
+            // leaving the object, so account for what was absorbed inside
+            if normal.is_some_and(|n| shadow.direction().dot(n) > 0.0) {
+                transmittance = transmittance
+                    * material
+                        .transmittance(hit.distance)
+                        .at_wavelength(wavelength);
+            }
+
+            let step = hit.distance + 1e-6;
+            remaining -= step;
+            shadow = Ray::new(shadow.point_at(step), *shadow.direction());
+        }
+
+        Color::BLACK
+    }
+
+    /// Pass the light arriving at `point` from every light and every
+    /// emissive shape other than `shape` to `visit`, accounting for shadows.
+    /// With [light samples](Self::set_light_samples) set and more sources
+    /// than that, only that many are picked, with one shadow ray each.
+    fn incident_light(
+        &self,
+        ray: &Ray,
+        shape: Option<ShapeId>,
+        point: &Point3D,
+        normal: &Vector3,
+        mut visit: impl FnMut(Incident),
+    ) {
+        let wavelength = ray.wavelength();
+        let receives_shadows = shape.is_none_or(|id| self.visibility[id.0].receives_shadows);
+        let mut rng = rand::thread_rng();
+
+        let mut shine = |sample: LightSample, color: Color| {
+            if sample.intensity <= 0.0 {
+                return;
+            }
+
+            let transmittance = if receives_shadows {
+                self.shadow_transmittance(&sample, point, normal, wavelength)
+            } else {
+                Color::WHITE
+            };
+            if transmittance != Color::BLACK {
+                visit(Incident {
+                    direction: sample.direction,
+                    radiance: color
+                        * transmittance
+                        * sample.tint.at_wavelength(wavelength)
+                        * sample.intensity,
+                });
+            }
+        };
+
+        let count = self.sources(shape).count();
+        if self.light_samples == 0 || count <= self.light_samples {
+            for source in self.sources(shape) {
+                match source {
+                    Source::Light(light) => {
+                        let color = light.color_at_wavelength(wavelength);
+                        for sample in light.sample_from(point) {
+                            shine(sample, color);
+                        }
+                    }
+                    Source::Emitter(emitter) => {
+                        let share = 1.0 / self.emission_samples as f64;
+                        for _ in 0..self.emission_samples {
+                            if let Some((sample, emitted)) =
+                                self.emitter_sample(emitter, point, normal, wavelength, &mut rng)
+                            {
+                                shine(sample, emitted * share);
+                            }
+                        }
+                    }
+                }
+            }
+            return;
+        }
+
+        // pick sources in proportion to their importance by walking evenly
+        // spaced steps through the running total, which needs no storage
+        let total = self
+            .sources(shape)
+            .map(|source| self.importance(source, point))
+            .sum::<f64>();
+        if total <= 0.0 {
+            return;
+        }
+
+        let samples = self.light_samples as f64;
+        let step = total / samples;
+        let mut next = rng.gen::<f64>() * step;
+        let mut running = 0.0;
+
+        for source in self.sources(shape) {
+            let importance = self.importance(source, point);
+            running += importance;
+
+            while next < running {
+                next += step;
+                let weight = total / (importance * samples);
+                let picked = match source {
+                    Source::Light(light) => light
+                        .sample_one(point)
+                        .map(|sample| (sample, light.color_at_wavelength(wavelength))),
+                    Source::Emitter(emitter) => {
+                        self.emitter_sample(emitter, point, normal, wavelength, &mut rng)
+                    }
+                };
+                if let Some((sample, color)) = picked {
+                    shine(sample, color * weight);
+                }
+            }
+        }
+    }
+
+    /// Everything that can light a shape: the lights linked to it, and the
+    /// emissive shapes other than itself. Ambient lights cast no shadow
+    /// rays, so they're left to [ambient_light](Self::ambient_light).
+    fn sources(&self, shape: Option<ShapeId>) -> impl Iterator<Item = Source<'_>> {
+        let lights = self
+            .lights
+            .iter()
+            .zip(&self.light_links)
+            .filter(move |(light, link)| {
+                !matches!(light, Light::Ambient(_) | Light::Hemisphere(_))
+                    && shape.is_none_or(|id| link.includes(id))
+            })
+            .map(|(light, _)| Source::Light(light));
+
+        let emitters = self
+            .shapes
+            .iter()
+            .enumerate()
+            .filter(move |(i, emitter)| {
+                shape != Some(ShapeId(*i)) && emitter.material().is_emissive()
+            })
+            .map(|(_, emitter)| Source::Emitter(emitter));
+
+        lights.chain(emitters)
+    }
+
+    /// A cheap estimate of how much light `source` sends to `point`,
+    /// ignoring shadows. It must be the same every time it's asked.
+    fn importance(&self, source: Source, point: &Point3D) -> f64 {
+        match source {
+            Source::Light(light) => light.importance(point),
+            Source::Emitter(emitter) => {
+                let radiance = luminance(&emitter.material().peak_emission());
+                match emitter.sample_towards(point, 0.5, 0.5) {
+                    Some((_, pdf)) => radiance / pdf,
+                    // seen edge on from here, so fall back to its size
+                    None => emitter.sample_surface(0.5, 0.5).map_or(0.0, |sample| {
+                        let r2 = (sample.point - point).norm().max(1e-6);
+                        radiance * emitter.area() / (4.0 * PI * r2)
+                    }),
+                }
+            }
+        }
+    }
+
+    /// A point on `emitter` as a light sample carrying all of its light,
+    /// stopping short of the emitter so it doesn't shadow itself, along with
+    /// the light emitted there.
+    fn emitter_sample(
+        &self,
+        emitter: &Shape,
+        point: &Point3D,
+        normal: &Vector3,
+        wavelength: Option<f64>,
+        rng: &mut impl Rng,
+    ) -> Option<(LightSample, Color)> {
+        let origin = point + normal * 1e-10_f64;
+        let (sample, pdf) = emitter.sample_towards(&origin, rng.gen(), rng.gen())?;
+
+        let to_light = sample.point - origin;
+        let distance = to_light.magnitude();
+        if distance < 1e-6 {
+            return None;
+        }
+
+        let coord = emitter
+            .texture_coord(&sample.point)
+            .with_position(object_space(emitter, &sample.point))
+            .with_normal(emitter.transform_normal_to_object(sample.normal));
+
+        let light = LightSample {
+            direction: to_light / distance,
+            distance: distance - 1e-6,
+            intensity: 1.0 / pdf,
+            tint: Color::WHITE,
+        };
+        Some((light, emitter.material().emission_at(&coord, wavelength)))
+    }
+
+    /// The light from ambient and hemisphere lights arriving at `point`,
+    /// which casts no shadows but may be darkened by ambient occlusion.
+    fn ambient_light(&self, shape: Option<ShapeId>, point: &Point3D, normal: &Vector3) -> Color {
+        let origin = point + normal * 1e-10_f64;
+
+        self.lights
+            .iter()
+            .zip(&self.light_links)
+            .filter(|(_, link)| shape.is_none_or(|id| link.includes(id)))
+            .filter_map(|(light, _)| light.ambient(normal))
+            .fold(Color::BLACK, |acc, (radiance, occlusion)| {
+                let visible = match occlusion {
+                    Some(occlusion) if occlusion.samples > 0 => {
+                        let open = occlusion
+                            .directions(normal)
+                            .into_iter()
+                            .filter(|direction| {
+                                let ray = Ray::new(origin, *direction);
+                                self.get_closest_intersection(&ray, |v| v.casts_shadows)
+                                    .is_none_or(|hit| hit.distance > occlusion.distance)
+                            })
+                            .count();
+                        open as f64 / occlusion.samples as f64
+                    }
+                    _ => 1.0,
+                };
+                acc + radiance * visible
+            })
+    }
+
     fn diffuse(
         &self,
-        shape: &Shape,
+        ray: &Ray,
+        shape: Option<ShapeId>,
+        material: &Material,
         point: &Point3D,
         normal: &Vector3,
         texture_coord: &TextureCoord,
     ) -> Color {
         let mut color = Color::BLACK;
+        let view = -ray.direction();
+        let surface_color = material
+            .color(&texture_coord)
+            .at_wavelength(ray.wavelength());
+        let specular_color = material.specular.at_wavelength(ray.wavelength());
 
-        for light in &self.lights {
-            let dir = light.direction_from(point);
-            let shadow = Ray::new(point + normal * 1e-10_f64, dir.clone());
+        color = color
+            + surface_color
+                * self
+                    .ambient_light(shape, point, normal)
+                    .at_wavelength(ray.wavelength())
+                * material.albedo;
 
-            let intensity = match self.get_closest_intersection(&shadow) {
-                Some(Intersection { distance: d, .. }) if light.distance(&point) > d => 0.0,
-                _ => light.intensity_at(&point),
-            };
+        self.incident_light(
+            ray,
+            shape,
+            point,
+            normal,
+            |Incident {
+                 direction: dir,
+                 radiance,
+             }| {
+                let cos = normal.dot(dir).max(0.0);
+                let reflected = material.albedo / PI;
+                color = color + surface_color * radiance * cos * reflected;
+
+                let highlight = material.highlight(normal, &dir, &view);
+                if highlight > 0.0 {
+                    color = color + specular_color * radiance * highlight;
+                }
+            },
+        );
+
+        color
+    }
 
-            let power = normal.dot(dir).max(0.0) * intensity;
-            let reflected = shape.material().albedo / PI;
-            color =
-                color + shape.material().color(&texture_coord) * light.color() * power * reflected;
+    fn principled(
+        &self,
+        ray: &Ray,
+        shape: Option<ShapeId>,
+        principled: &Principled,
+        point: &Point3D,
+        normal: &Vector3,
+        texture_coord: &TextureCoord,
+    ) -> Color {
+        let mut params = principled.sample(texture_coord);
+        params.base_color = params.base_color.at_wavelength(ray.wavelength());
+        let view = -ray.direction();
+
+        // light the side of the surface we're looking at
+        let facing = if normal.dot(view) < 0.0 {
+            -normal
+        } else {
+            *normal
+        };
+
+        let mut color = params.diffuse_albedo()
+            * self
+                .ambient_light(shape, point, &facing)
+                .at_wavelength(ray.wavelength());
+
+        self.incident_light(
+            ray,
+            shape,
+            point,
+            &facing,
+            |Incident {
+                 direction: dir,
+                 radiance,
+             }| {
+                let cos = facing.dot(dir).max(0.0);
+                color = color + params.eval(&facing, &view, &dir) * radiance * cos;
+            },
+        );
+
+        let reflectance = params.reflectance(&facing, &view);
+        if reflectance.r.max(reflectance.g).max(reflectance.b) > 1e-3 {
+            let reflection = ray.reflect(&facing, point, 1e-10_f64);
+            color = color + self.color_for(&reflection) * reflectance;
+        }
+
+        if params.transmission > 0.0 {
+            let kr = self.fresnel(ray, normal, principled.ior);
+            if kr < 1.0 {
+                if let Some(transmission) = ray.refract(normal, point, 1e-10_f64, principled.ior) {
+                    color = color
+                        + self.color_for(&transmission)
+                            * params.base_color
+                            * ((1.0 - kr) * params.transmission);
+                }
+            }
         }
 
         color
@@ -252,6 +767,33 @@ impl Scene {
         (s * s + p * p) / 2.0
     }
 
+    /// Like [fresnel](Self::fresnel), but for a surface coated with a thin film
+    /// whose reflectance depends on wavelength. Rays tagged with a wavelength
+    /// get that wavelength's reflectance in every channel.
+    fn thin_film_fresnel(
+        &self,
+        ray: &Ray,
+        normal: &Vector3,
+        film: &ThinFilm,
+        substrate: Substrate,
+        texture_coord: &TextureCoord,
+    ) -> Color {
+        let cos_i = -ray.direction().dot(normal);
+
+        match ray.wavelength() {
+            Some(wavelength) => {
+                let r = film.reflectance(texture_coord, cos_i, substrate, wavelength);
+                Color::new(r, r, r)
+            }
+            None => wavelength_samples(THIN_FILM_SAMPLES).into_iter().fold(
+                Color::BLACK,
+                |acc, (wavelength, weight)| {
+                    acc + weight * film.reflectance(texture_coord, cos_i, substrate, wavelength)
+                },
+            ),
+        }
+    }
+
     fn color_at(&self, ray: &Ray, intersection: &Intersection) -> Option<Color> {
         if ray.generation() >= self.max_generations {
             return Some(Color::BLACK);
@@ -262,60 +804,706 @@ impl Scene {
             None => ray.point_at(intersection.distance),
         };
 
-        let hit_normal = match intersection.normal {
+        let mut hit_normal = match intersection.normal {
             Some(normal) => normal,
             None => intersection.obj.normal_at(&point)?,
         };
 
-        let texture_coord = match intersection.tex_coord {
+        let mut texture_coord = match intersection.tex_coord {
             Some(coord) => coord,
             None => intersection.obj.texture_coord(&point),
-        };
+        }
+        .with_position(object_space(intersection.obj, &point))
+        .with_normal(intersection.obj.transform_normal_to_object(hit_normal));
+
+        let derivatives = intersection
+            .derivatives
+            .or_else(|| intersection.obj.uv_derivatives(&point));
+
+        if let (Some(differential), Some((dpdu, dpdv))) = (ray.differential(), derivatives) {
+            if let Some((dpdx, dpdy)) = differential.offsets(&point, &hit_normal) {
+                texture_coord = texture_coord.with_footprint(
+                    uv_offset(&dpdx, &dpdu, &dpdv),
+                    uv_offset(&dpdy, &dpdu, &dpdv),
+                );
+            }
+        }
+
+        if let Some(map) = &intersection.obj.material().normal_map {
+            let tangent = intersection
+                .tangent
+                .or_else(|| intersection.obj.tangent_at(&point));
+
+            if let Some(tangent) = tangent {
+                hit_normal = map.perturb(&hit_normal, &tangent, &texture_coord);
+            }
+        }
+
+        Some(self.shade(
+            ray,
+            intersection,
+            intersection.obj.material(),
+            &point,
+            &hit_normal,
+            &texture_coord,
+        ))
+    }
+
+    /// Shade a hit using `material`, which may be one layer of the material
+    /// attached to the intersected shape.
+    fn shade(
+        &self,
+        ray: &Ray,
+        intersection: &Intersection,
+        material: &Material,
+        point: &Point3D,
+        hit_normal: &Vector3,
+        texture_coord: &TextureCoord,
+    ) -> Color {
+        if let Some(blend) = &material.blend {
+            // only trace the branches that show, so nested blends with hard
+            // masks don't shade every layer
+            let mask = blend.mask.value(texture_coord).clamp(0.0, 1.0);
+            let shade = |material| {
+                self.shade(
+                    ray,
+                    intersection,
+                    material,
+                    point,
+                    hit_normal,
+                    texture_coord,
+                )
+            };
+            return if mask <= 0.0 {
+                shade(&blend.base)
+            } else if mask >= 1.0 {
+                shade(&blend.layer)
+            } else {
+                shade(&blend.base).mix(shade(&blend.layer), mask)
+            };
+        }
+
+        let emitted = material.emitted_at(ray.wavelength());
 
-        let color = match intersection.obj.material().surface {
-            Surface::Diffuse => self.diffuse(intersection.obj, &point, &hit_normal, &texture_coord),
+        if let Some(principled) = &material.principled {
+            let color = self.principled(
+                ray,
+                intersection.shape,
+                principled,
+                point,
+                hit_normal,
+                texture_coord,
+            );
+            return color + emitted;
+        }
+
+        let color = match material.surface {
+            Surface::Diffuse => self.diffuse(
+                ray,
+                intersection.shape,
+                material,
+                point,
+                hit_normal,
+                texture_coord,
+            ),
             Surface::Reflective(reflectivity) => {
-                let mut color = self.diffuse(intersection.obj, &point, &hit_normal, &texture_coord);
-                let reflection = ray.reflect(&hit_normal, &point, 1e-10_f64);
+                let mut color = self.diffuse(
+                    ray,
+                    intersection.shape,
+                    material,
+                    point,
+                    hit_normal,
+                    texture_coord,
+                );
+                let reflection = ray.reflect(hit_normal, point, 1e-10_f64);
                 color = color * (1.0 - reflectivity);
-                color + self.color_for(&reflection) * reflectivity
+
+                match &material.thin_film {
+                    Some(film) => {
+                        let kr = self.thin_film_fresnel(
+                            ray,
+                            hit_normal,
+                            film,
+                            Substrate::Conductor(reflectivity),
+                            texture_coord,
+                        );
+                        color + self.color_for(&reflection) * kr
+                    }
+                    None => color + self.color_for(&reflection) * reflectivity,
+                }
+            }
+            Surface::Refractive { .. }
+                if material.dispersion.is_some() && ray.wavelength().is_none() =>
+            {
+                // split the ray into its spectrum, and let each wavelength
+                // find its own way through the object
+                wavelength_samples(self.dispersion_samples)
+                    .into_iter()
+                    .map(|(wavelength, weight)| {
+                        let tagged = ray.clone().with_wavelength(wavelength);
+                        let emitted = material.emitted_at(Some(wavelength));
+                        let color = self.shade(
+                            &tagged,
+                            intersection,
+                            material,
+                            point,
+                            hit_normal,
+                            texture_coord,
+                        );
+                        (color - emitted) * weight
+                    })
+                    .fold(Color::BLACK, |acc, color| acc + color)
             }
             Surface::Refractive {
                 index,
                 transparency,
             } => {
+                let index = material.refractive_index(index, ray.wavelength());
                 let mut refract_color = Color::BLACK;
-                let kr = self.fresnel(ray, &hit_normal, index);
-                let surface_color = intersection.obj.material().color(&texture_coord);
+                let kr = self.fresnel(ray, hit_normal, index);
+                // films coat the outside, so only rays arriving from outside
+                // see them
+                let film_kr = match &material.thin_film {
+                    Some(film) if ray.direction().dot(hit_normal) < 0.0 => {
+                        Some(self.thin_film_fresnel(
+                            ray,
+                            hit_normal,
+                            film,
+                            Substrate::Dielectric(index),
+                            texture_coord,
+                        ))
+                    }
+                    _ => None,
+                };
+                let surface_color = material
+                    .color(texture_coord)
+                    .at_wavelength(ray.wavelength());
 
                 if kr < 1.0 {
-                    if let Some(transmission) = ray.refract(&hit_normal, &point, 1e-10_f64, index) {
+                    if let Some(transmission) = ray.refract(hit_normal, point, 1e-10_f64, index) {
                         refract_color = self.color_for(&transmission);
                     }
                 }
 
-                let reflection = ray.reflect(&hit_normal, &point, 1e-10_f64);
+                let reflection = ray.reflect(hit_normal, point, 1e-10_f64);
                 let reflect_color = self.color_for(&reflection);
-                let color = (1.0 - kr) * refract_color + kr * reflect_color;
+                let mut color = match film_kr {
+                    Some(film_kr) => {
+                        refract_color * (Color::WHITE - film_kr) + reflect_color * film_kr
+                    }
+                    None => (1.0 - kr) * refract_color + kr * reflect_color,
+                };
+
+                // we're leaving the object, so account for what was absorbed
+                // along the way in
+                if ray.direction().dot(hit_normal) > 0.0 {
+                    color = color
+                        * material
+                            .transmittance(intersection.distance)
+                            .at_wavelength(ray.wavelength());
+                }
 
                 color * transparency * surface_color
             }
         };
 
-        Some(color)
+        color + emitted
     }
 
     fn color_for(&self, ray: &Ray) -> Color {
-        match self.get_closest_intersection(ray) {
+        let hit = self.get_closest_intersection(ray, |v| {
+            if ray.is_primary() {
+                v.camera
+            } else {
+                v.reflections
+            }
+        });
+
+        // lights with a surface can be seen directly
+        let light = self
+            .lights
+            .iter()
+            .filter_map(|light| light.intersect(ray).map(|d| (d, light)))
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        if let Some((d, light)) = light {
+            if hit.as_ref().is_none_or(|hit| d < hit.distance) {
+                return light.radiance(ray.wavelength());
+            }
+        }
+
+        match hit {
             Some(intersection) => self.color_at(ray, &intersection).unwrap_or(Color::BLACK),
-            None => self.background.clone(),
+            None => self
+                .lights
+                .iter()
+                .find_map(|light| light.background(ray.direction()))
+                .unwrap_or(self.background)
+                .at_wavelength(ray.wavelength()),
         }
     }
 
-    fn get_closest_intersection(&self, ray: &Ray) -> Option<Intersection> {
+    /// The color seen along a camera ray, which in spectral mode is assembled
+    /// from one traced ray per wavelength.
+    fn primary_color(&self, ray: &Ray) -> Color {
+        let film = match &self.spectral {
+            Some(film) => film,
+            None => return self.color_for(ray),
+        };
+
+        let mut rng = rand::thread_rng();
+        let radiance = (0..film.samples())
+            .map(|i| {
+                let wavelength = film.wavelength(i, rng.gen());
+                let tagged = ray.clone().with_wavelength(wavelength);
+                // tagged rays carry their radiance in every channel
+                (wavelength, self.color_for(&tagged).r)
+            })
+            .collect::<Vec<(f64, f64)>>();
+
+        film.develop(&radiance)
+    }
+
+    /// The closest hit along `ray` among the shapes whose visibility passes
+    /// `visible`.
+    fn get_closest_intersection<F>(&self, ray: &Ray, visible: F) -> Option<Intersection>
+    where
+        F: Fn(&Visibility) -> bool,
+    {
         self.shapes
             .iter()
-            .filter_map(|s| s.intersect(ray))
+            .zip(&self.visibility)
+            .enumerate()
+            .filter(|(_, (_, v))| visible(v))
+            .filter_map(|(i, (s, _))| {
+                let mut hit = self.intersect_opaque(s, ray)?;
+                hit.shape = Some(ShapeId(i));
+                Some(hit)
+            })
             .min_by(|a, b| a.partial_cmp(&b).unwrap())
     }
+
+    /// Intersect `shape`, passing through any parts its material cuts out.
+    fn intersect_opaque<'a>(&self, shape: &'a Shape, ray: &Ray) -> Option<Intersection<'a>> {
+        let material = shape.material();
+        if !material.has_cutout() {
+            return shape.intersect(ray);
+        }
+
+        let mut travelled = 0.0;
+        let mut current = ray.clone();
+
+        for _ in 0..MAX_CUTOUT_CROSSINGS {
+            let mut hit = shape.intersect(&current)?;
+            let point = hit
+                .location
+                .unwrap_or_else(|| current.point_at(hit.distance));
+            let normal = hit
+                .normal
+                .or_else(|| shape.normal_at(&point))
+                .unwrap_or(Vector3::ZERO);
+            let coord = hit
+                .tex_coord
+                .unwrap_or_else(|| shape.texture_coord(&point))
+                .with_position(object_space(shape, &point))
+                .with_normal(shape.transform_normal_to_object(normal));
+
+            if material.is_opaque_at(&coord) {
+                hit.distance += travelled;
+                return Some(hit);
+            }
+
+            let step = hit.distance + 1e-6;
+            travelled += step;
+            current = Ray::new(current.point_at(step), *current.direction());
+        }
+
+        None
+    }
+}
+
+/// `point` in the object space of `shape`, for solid textures.
+fn object_space(shape: &Shape, point: &Point3D) -> Point3D {
+    match shape.world_to_object() {
+        Some(m) => m * point,
+        None => *point,
+    }
+}
+
+/// Express `offset`, a small step across a surface, in texture coordinates
+/// given how the surface moves per unit of `u` and `v`.
+fn uv_offset(offset: &Vector3, dpdu: &Vector3, dpdv: &Vector3) -> Vector2 {
+    // least squares, since the step need not lie exactly in the surface
+    let (uu, uv, vv) = (dpdu.dot(dpdu), dpdu.dot(dpdv), dpdv.dot(dpdv));
+    let det = uu * vv - uv * uv;
+    if det.abs() < 1e-12 {
+        return Vector2::ZERO;
+    }
+
+    let (ou, ov) = (offset.dot(dpdu), offset.dot(dpdv));
+    Vector2::new([(vv * ou - uv * ov) / det, (uu * ov - uv * ou) / det])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::{AmbientLight, PointLight};
+    use crate::material::{Checker, Node};
+    use crate::shape::{Plane, Sphere};
+
+    /// The average color seen along `ray` over `n` renders, for scenes with
+    /// random sampling.
+    fn mean_color(scene: &Scene, ray: &Ray, n: usize) -> Color {
+        (0..n).fold(Color::BLACK, |acc, _| acc + scene.color_for(ray)) * (1.0 / n as f64)
+    }
+
+    /// A floor through the origin made of `material`, and a ray looking at
+    /// the origin from the side.
+    fn floor_scene_with(material: Material) -> (Scene, Ray, ShapeId) {
+        let mut scene = Scene::new();
+        let floor =
+            scene.add_shape(Plane::new(Point3D::default(), Vector3::J).with_material(material));
+
+        let eye = Point3D::new(3.0, 1.0, 0.0);
+        let ray = Ray::new(eye, (Point3D::default() - eye).normalize());
+        (scene, ray, floor)
+    }
+
+    /// A white floor through the origin, seen from the side.
+    fn floor_scene() -> (Scene, Ray, ShapeId) {
+        floor_scene_with(Material::new(Color::WHITE))
+    }
+
+    /// A sphere glowing white with radiance `strength`.
+    fn glowing_sphere(center: Point3D, radius: f64, strength: f64) -> Sphere {
+        Sphere::new(center, radius)
+            .with_material(Material::new(Color::BLACK).with_emission(Color::WHITE, strength))
+    }
+
+    #[test]
+    fn principled_surfaces_follow_the_brdf() {
+        let principled = Principled::new(Color::new(0.8, 0.4, 0.2))
+            .with_metallic(0.3)
+            .with_roughness(1.0);
+        let (mut scene, ray, _) =
+            floor_scene_with(Material::new(Color::WHITE).with_principled(principled.clone()));
+        let light = Light::from(PointLight::new(Point3D::new(0.0, 2.0, 0.0)));
+        scene.add_light(light.clone());
+
+        // the light is straight above the point seen, and a fully rough
+        // surface traces no reflection ray
+        let params = principled.sample(&TextureCoord::new(Default::default(), 1.0));
+        let expected = params.eval(&Vector3::J, &-ray.direction(), &Vector3::J)
+            * light.intensity_at(&Point3D::default());
+
+        let color = scene.color_for(&ray);
+        assert!((color.r - expected.r).abs() < 1e-9 && (color.b - expected.b).abs() < 1e-9);
+        assert!(color.r > color.b);
+    }
+
+    #[test]
+    fn principled_transmission_shows_what_is_behind() {
+        // a glass sheet over nothing but a white background
+        let mut scene = Scene::new();
+        scene.background = Color::WHITE;
+        scene.add_shape(
+            Plane::new(Point3D::new(0.0, 0.5, 0.0), Vector3::J).with_material(
+                Material::new(Color::WHITE).with_principled(
+                    Principled::new(Color::RED)
+                        .with_roughness(1.0)
+                        .with_transmission(1.0),
+                ),
+            ),
+        );
+
+        let eye = Point3D::new(3.0, 1.0, 0.0);
+        let ray = Ray::new(eye, (Point3D::default() - eye).normalize());
+        let kr = scene.fresnel(&ray, &Vector3::J, 1.45);
+        let color = scene.color_for(&ray);
+        assert!(kr > 0.0 && kr < 1.0);
+        assert!(
+            (color.r - (1.0 - kr)).abs() < 1e-9 && color.g == 0.0,
+            "{:?}",
+            color
+        );
+    }
+
+    #[test]
+    fn emissive_shapes_light_other_shapes() {
+        let (mut scene, ray, _) = floor_scene();
+        scene.add_shape(glowing_sphere(Point3D::new(0.0, 2.0, 0.0), 0.5, 4.0));
+
+        // a sphere of radiance L subtending half-angle α gives E = πL sin²α,
+        // and a white diffuse surface reflects E / π
+        let expected = 4.0 * 0.25 * 0.25;
+        let color = mean_color(&scene, &ray, 200);
+        assert!((color.g / expected - 1.0).abs() < 0.02, "{:?}", color);
+    }
+
+    #[test]
+    fn thicker_glass_absorbs_more() {
+        let shade = |radius: f64| {
+            let glow = Material::new(Color::BLACK).with_emission(Color::WHITE, 1.0);
+            let (mut scene, ray, _) = floor_scene_with(glow);
+            // on the camera ray, so it's crossed through the middle
+            scene.add_shape(
+                Sphere::new(Point3D::new(1.5, 0.5, 0.0), radius).with_material(
+                    Material::new(Color::WHITE)
+                        .with_surface(Surface::Refractive {
+                            index: 1.0,
+                            transparency: 1.0,
+                        })
+                        .with_absorption_color(Color::new(0.5, 0.5, 0.5), 1.0),
+                ),
+            );
+            scene.color_for(&ray).g
+        };
+
+        let (thin, thick) = (shade(0.1), shade(0.4));
+        assert!((thin - 0.5_f64.powf(0.2)).abs() < 1e-6, "{}", thin);
+        assert!((thick - 0.5_f64.powf(0.8)).abs() < 1e-6, "{}", thick);
+    }
+
+    #[test]
+    fn layers_follow_the_mask() {
+        let glow = |color| Material::new(Color::BLACK).with_emission(color, 1.0);
+        let shade = |mask: f64| {
+            let (scene, ray, _) =
+                floor_scene_with(glow(Color::RED).with_layer(glow(Color::BLUE), mask));
+            scene.color_for(&ray)
+        };
+
+        assert_eq!(shade(0.0), Color::RED);
+        assert_eq!(shade(1.0), Color::BLUE);
+        assert_eq!(shade(0.5), Color::new(0.5, 0.0, 0.5));
+    }
+
+    #[test]
+    fn emissive_layers_light_where_they_show() {
+        let shade = |material: Material| {
+            let (mut scene, ray, _) = floor_scene();
+            scene.add_shape(Sphere::new(Point3D::new(0.0, 2.0, 0.0), 0.5).with_material(material));
+            mean_color(&scene, &ray, 200)
+        };
+        let dark = Material::new(Color::BLACK);
+        let glow = dark.clone().with_emission(Color::WHITE, 4.0);
+
+        let shown = shade(dark.clone().with_layer(glow.clone(), 1.0));
+        assert!(
+            (shown.g / (4.0 * 0.25 * 0.25) - 1.0).abs() < 0.02,
+            "{:?}",
+            shown
+        );
+        assert_eq!(shade(glow.with_layer(dark, 1.0)), Color::BLACK);
+    }
+
+    #[test]
+    fn cutouts_cast_shadows_around_their_holes() {
+        // a stencil facing down between the floor and the light, with holes
+        // in the dark checks of a triplanar projection
+        let stencil = Material::default().with_opacity(Node::triplanar(
+            Checker::new(Color::WHITE).with_secondary(Color::BLACK),
+            4.0,
+        ));
+        let shade = |light_x: f64| {
+            let (mut scene, ray, _) = floor_scene();
+            scene.add_shape(
+                Plane::new(Point3D::new(0.5, 1.0, 0.0), -Vector3::J).with_material(stencil.clone()),
+            );
+            scene.add_light(PointLight::new(Point3D::new(light_x, 2.0, 0.5)));
+            scene.color_for(&ray)
+        };
+
+        // the shadow rays cross the stencil at x = 0.25 and x = 0.75
+        assert!(shade(0.5).g > 0.0);
+        assert_eq!(shade(1.5), Color::BLACK);
+    }
+
+    #[test]
+    fn emitters_shine_through_colored_glass() {
+        let (mut scene, _, _) = floor_scene();
+        scene.add_shape(glowing_sphere(Point3D::new(0.0, 2.0, 0.0), 0.5, 4.0));
+        scene.add_shape(
+            Plane::new(Point3D::new(0.0, 1.0, 0.0), -Vector3::J).with_material(
+                Material::new(Color::RED).with_surface(Surface::Refractive {
+                    index: 1.0,
+                    transparency: 1.0,
+                }),
+            ),
+        );
+        // look at the floor from below the pane
+        let eye = Point3D::new(3.0, 0.5, 0.0);
+        let ray = Ray::new(eye, (Point3D::default() - eye).normalize());
+
+        let color = mean_color(&scene, &ray, 50);
+        assert!(
+            color.r > 0.0 && color.g == 0.0 && color.b == 0.0,
+            "{:?}",
+            color
+        );
+    }
+
+    /// The white floor with a point light above the spot the ray looks at.
+    fn lit_floor_scene() -> (Scene, Ray, ShapeId, LightId) {
+        let (mut scene, ray, floor) = floor_scene();
+        let light = scene.add_light(PointLight::new(Point3D::new(0.0, 2.0, 0.0)));
+        (scene, ray, floor, light)
+    }
+
+    #[test]
+    fn light_links_choose_the_shapes_lit() {
+        let shade = |link: fn(ShapeId, ShapeId) -> LightLink| {
+            let (mut scene, ray, floor, light) = lit_floor_scene();
+            let other = scene.add_shape(Sphere::new(Point3D::new(0.0, 0.0, 9.0), 0.5));
+            scene.link_light(light, link(floor, other));
+            scene.color_for(&ray)
+        };
+
+        assert!(shade(|floor, _| LightLink::Only(vec![floor])).g > 0.0);
+        assert_eq!(shade(|_, other| LightLink::Only(vec![other])), Color::BLACK);
+        assert_eq!(
+            shade(|floor, _| LightLink::Except(vec![floor])),
+            Color::BLACK
+        );
+        assert!(shade(|_, other| LightLink::Except(vec![other])).g > 0.0);
+    }
+
+    #[test]
+    fn visibility_controls_shadows() {
+        let shade = |blocker: Visibility, floor_visibility: Visibility| {
+            let (mut scene, ray, floor, _) = lit_floor_scene();
+            let id = scene.add_shape(Sphere::new(Point3D::new(0.0, 1.0, 0.0), 0.3));
+            scene.set_visibility(id, blocker);
+            scene.set_visibility(floor, floor_visibility);
+            scene.color_for(&ray)
+        };
+        let all = Visibility::default();
+
+        assert_eq!(shade(all, all), Color::BLACK);
+        let no_cast = Visibility {
+            casts_shadows: false,
+            ..all
+        };
+        assert!(shade(no_cast, all).g > 0.0);
+        let no_receive = Visibility {
+            receives_shadows: false,
+            ..all
+        };
+        assert!(shade(all, no_receive).g > 0.0);
+    }
+
+    #[test]
+    fn hidden_shapes_are_skipped_by_camera_rays() {
+        let (scene, ray, _, _) = lit_floor_scene();
+        let floor = scene.color_for(&ray);
+
+        let shade = |visibility| {
+            let (mut scene, ray, _, _) = lit_floor_scene();
+            // halfway along the camera ray, well away from the light
+            let id = scene.add_shape(
+                Sphere::new(Point3D::new(1.5, 0.5, 0.0), 0.2)
+                    .with_material(Material::new(Color::RED)),
+            );
+            scene.set_visibility(id, visibility);
+            scene.color_for(&ray)
+        };
+
+        let hidden = Visibility {
+            camera: false,
+            ..Visibility::default()
+        };
+        assert_eq!(shade(hidden), floor);
+        assert_ne!(shade(Visibility::default()), floor);
+    }
+
+    /// The floor lit by a row of point lights of different strengths and a
+    /// glowing sphere.
+    fn many_lights_scene() -> (Scene, Ray) {
+        let (mut scene, ray, _) = floor_scene();
+        for i in 0..6 {
+            let x = i as f64 - 2.5;
+            scene.add_light(
+                PointLight::new(Point3D::new(x, 1.0 + 0.5 * i as f64, 1.0))
+                    .intensity(50.0 * (i + 1) as f64),
+            );
+        }
+        scene.add_shape(glowing_sphere(Point3D::new(0.0, 1.5, -1.0), 0.3, 8.0));
+        (scene, ray)
+    }
+
+    #[test]
+    fn picking_lights_averages_to_every_light() {
+        let (mut scene, ray) = many_lights_scene();
+        let every = mean_color(&scene, &ray, 400);
+
+        scene.set_light_samples(2);
+        let picked = mean_color(&scene, &ray, 4000);
+        assert!(
+            (picked.g / every.g - 1.0).abs() < 0.03,
+            "{:?} {:?}",
+            picked,
+            every
+        );
+    }
+
+    #[test]
+    fn enough_light_samples_sample_every_light() {
+        let (mut scene, ray, _, _) = lit_floor_scene();
+        for i in 0..3 {
+            scene.add_light(PointLight::new(Point3D::new(i as f64, 2.0, 1.0)));
+        }
+        let every = scene.color_for(&ray);
+
+        scene.set_light_samples(4);
+        assert_eq!(scene.color_for(&ray), every);
+        scene.set_light_samples(10);
+        assert_eq!(scene.color_for(&ray), every);
+    }
+
+    #[test]
+    fn ambient_light_fills_unlit_faces() {
+        let fill = AmbientLight::new()
+            .color(Color::new(0.5, 1.0, 1.0))
+            .intensity(0.4);
+        let paint = Color::new(0.2, 0.4, 0.8);
+        let shade = |material: Material| {
+            let (mut scene, ray, _) = floor_scene_with(material);
+            scene.add_light(fill.clone());
+            scene.color_for(&ray)
+        };
+
+        let expected = fill.radiance() * 0.5 * paint;
+        let diffuse = shade(Material::new(paint).with_albedo(0.5));
+        assert!((diffuse.r - expected.r).abs() < 1e-9 && (diffuse.b - expected.b).abs() < 1e-9);
+
+        let principled = shade(
+            Material::new(Color::WHITE).with_principled(
+                Principled::new(paint)
+                    .with_metallic(0.5)
+                    .with_roughness(1.0),
+            ),
+        );
+        assert!(
+            (principled.r - expected.r).abs() < 1e-9 && (principled.b - expected.b).abs() < 1e-9
+        );
+    }
+
+    #[test]
+    fn ambient_occlusion_darkens_creases() {
+        let shade = |wall: bool| {
+            let (mut scene, ray, _) = floor_scene();
+            scene.add_light(
+                AmbientLight::new()
+                    .intensity(1.0)
+                    .ambient_occlusion(256, 1.0),
+            );
+            if wall {
+                scene.add_shape(Plane::new(Point3D::new(-0.1, 0.0, 0.0), Vector3::I));
+            }
+            mean_color(&scene, &ray, 20)
+        };
+
+        assert_eq!(shade(false), Color::WHITE);
+        assert!(shade(true).g < 0.8);
+    }
 }
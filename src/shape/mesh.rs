@@ -4,17 +4,19 @@ use crate::{
     error::{RTError, Result},
     geo::GeoMesh,
     material::{Material, TextureCoord, Textured},
-    math::{Matrix4, Point2D, Point3D, Vector2, Vector3},
+    math::{Matrix4, Point2D, Point3D, Vector2, Vector3, EPSILON},
     ply::Ply,
     ray::Ray,
 };
 
 use super::{
-    triangle::pre_calc_traingle_intersect,
+    triangle::{pre_calc_traingle_intersect, uniform_barycentric},
     BoundingBox,
     Intersect,
     Intersection,
+    Sampleable,
     Shape,
+    SurfaceSample,
     Transformable
 };
 
@@ -23,6 +25,7 @@ pub struct Vertex {
     pub point: Point3D,
     pub normal: Vector3,
     pub texture_coord: Point2D,
+    pub tangent: Vector3,
 }
 
 impl Vertex {
@@ -31,6 +34,7 @@ impl Vertex {
             point,
             normal,
             texture_coord,
+            tangent: Vector3::ZERO,
         }
     }
 
@@ -62,6 +66,8 @@ pub struct TriangleMesh {
     triangles: Vec<TriAddr>,
     triangle_normals: Vec<Vector3>,
     bounding_box: BoundingBox,
+    // running total of triangle areas, for picking triangles by area
+    area_cdf: Vec<f64>,
     material: Material,
     wto: Matrix4,
     otw: Matrix4,
@@ -91,6 +97,66 @@ impl TriangleMesh {
             self.vertices.get(t.v2)?,
         ))
     }
+
+    /// How a triangle's surface moves per unit of `u` and `v`, or `None` if
+    /// its texture coordinates are degenerate.
+    fn triangle_uv_derivatives(&self, t: &TriAddr) -> Option<(Vector3, Vector3)> {
+        let uv0 = Vector2::from(self.vertices[t.v0].texture_coord);
+        let duv1 = Vector2::from(self.vertices[t.v1].texture_coord) - uv0;
+        let duv2 = Vector2::from(self.vertices[t.v2].texture_coord) - uv0;
+
+        let det = duv1.x() * duv2.y() - duv2.x() * duv1.y();
+        if det.abs() < EPSILON {
+            return None;
+        }
+
+        Some((
+            (t.v0v1 * duv2.y() - t.v0v2 * duv1.y()) / det,
+            (t.v0v2 * duv1.x() - t.v0v1 * duv2.x()) / det,
+        ))
+    }
+
+    /// Derive per-vertex tangents (the direction of increasing `u`) from the
+    /// texture coordinates, for normal mapping.
+    fn compute_tangents(&mut self) {
+        let mut tangents = vec![Vector3::ZERO; self.vertices.len()];
+
+        for t in &self.triangles {
+            if let Some((tangent, _)) = self.triangle_uv_derivatives(t) {
+                for &v in [t.v0, t.v1, t.v2].iter() {
+                    tangents[v] = tangents[v] + tangent;
+                }
+            }
+        }
+
+        for (vertex, tangent) in self.vertices.iter_mut().zip(tangents) {
+            let n = vertex.normal;
+            let mut t = tangent - n * n.dot(tangent);
+
+            // without usable texture coordinates any direction along the
+            // surface will do
+            if t.magnitude() < EPSILON {
+                t = n.cross(Vector3::J);
+                if t.magnitude() < EPSILON {
+                    t = n.cross(Vector3::I);
+                }
+            }
+
+            vertex.tangent = t.normalize();
+        }
+    }
+
+    fn compute_area_cdf(&mut self) {
+        let mut total = 0.0;
+        self.area_cdf = self
+            .triangles
+            .iter()
+            .map(|t| {
+                total += t.v0v1.cross(t.v0v2).magnitude() / 2.0;
+                total
+            })
+            .collect();
+    }
 }
 
 impl Intersect for TriangleMesh {
@@ -132,11 +198,22 @@ impl Intersect for TriangleMesh {
                     }
                 };
 
-                let intersect = Intersection::new(dist, shape_ref)
+                let tangent = (1.0 - uv.x() - uv.y()) * v0.tangent
+                    + uv.x() * v1.tangent
+                    + uv.y() * v2.tangent;
+
+                let mut intersect = Intersection::new(dist, shape_ref)
                     .location(point)
                     .normal(normal)
+                    .tangent(tangent)
                     .texture_coord(TextureCoord::new(hit_coord.into(), self.material.scale));
 
+                if let Some((dpdu, dpdv)) =
+                    self.triangle_uv_derivatives(&self.triangles[triangle_index])
+                {
+                    intersect = intersect.derivatives(dpdu, dpdv);
+                }
+
                 return Some(intersect);
             }
         }
@@ -155,6 +232,41 @@ impl Textured for TriangleMesh {
     }
 }
 
+impl Sampleable for TriangleMesh {
+    fn area(&self) -> f64 {
+        self.area_cdf.last().copied().unwrap_or(0.0)
+    }
+
+    fn sample_surface(&self, u: f64, v: f64) -> Option<SurfaceSample> {
+        let target = u * self.area();
+        let index = self
+            .area_cdf
+            .partition_point(|a| *a < target)
+            .min(self.num_triangles.checked_sub(1)?);
+
+        // reuse what's left of u to place the point within the triangle
+        let start = if index > 0 {
+            self.area_cdf[index - 1]
+        } else {
+            0.0
+        };
+        let width = self.area_cdf[index] - start;
+        let u = if width > 0.0 {
+            ((target - start) / width).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        let tri = self.triangles.get(index)?;
+        let (b1, b2) = uniform_barycentric(u, v);
+
+        Some(SurfaceSample {
+            point: self.vertices[tri.v0].point + tri.v0v1 * b1 + tri.v0v2 * b2,
+            normal: tri.v0v1.cross(tri.v0v2).normalize(),
+        })
+    }
+}
+
 impl Transformable for TriangleMesh {
     fn transform(&mut self, matrix: &Matrix4) -> Result<()> {
         self.wto = matrix.inverse().ok_or_else(|| {
@@ -193,6 +305,8 @@ impl Transformable for TriangleMesh {
         }
 
         self.bounding_box = BoundingBox::new(min.into(), max.into());
+        self.compute_area_cdf();
+        self.compute_tangents();
 
         Ok(())
     }
@@ -248,17 +362,22 @@ impl From<GeoMesh> for TriangleMesh {
             k += geo.face_index[i];
         }
 
-        Self {
+        let mut mesh = Self {
             num_triangles,
             vertices: geo.vertices,
             triangles,
             triangle_normals,
             bounding_box: geo.bounding_box,
+            area_cdf: Vec::new(),
             material: Material::default(),
             wto: Matrix4::I,
             otw: Matrix4::I,
             shading_mode: ShadingMode::Flat,
-        }
+        };
+
+        mesh.compute_area_cdf();
+        mesh.compute_tangents();
+        mesh
     }
 }
 
@@ -293,17 +412,22 @@ impl From<Ply> for TriangleMesh {
             }
         }
 
-        Self {
+        let mut mesh = Self {
             num_triangles,
             vertices: ply.vertices,
             triangles,
             triangle_normals,
             bounding_box: ply.bounding_box,
+            area_cdf: Vec::new(),
             material: Material::default(),
             wto: Matrix4::I,
             otw: Matrix4::I,
             shading_mode: ShadingMode::Flat,
-        }
+        };
+
+        mesh.compute_area_cdf();
+        mesh.compute_tangents();
+        mesh
     }
 }
 
@@ -328,3 +452,55 @@ impl TriangleMesh {
         // let mid = (max - min) / 2.0  + min;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a unit square in the xz plane facing up, with u running along -z
+    fn square() -> TriangleMesh {
+        let uv = [(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)];
+        let points = [
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(1.0, 0.0, 0.0),
+            Point3D::new(1.0, 0.0, -1.0),
+            Point3D::new(0.0, 0.0, -1.0),
+        ];
+        let vertices = points
+            .iter()
+            .zip(uv.iter())
+            .map(|(&p, &(u, v))| Vertex::new(p, Vector3::J, Point2D::new(u, v)))
+            .collect();
+
+        TriangleMesh::from(GeoMesh {
+            num_faces: 1,
+            face_index: vec![4],
+            vertex_index: vec![0, 1, 2, 3],
+            vertices,
+            face_normals: vec![Vector3::J],
+            bounding_box: BoundingBox::new(
+                Vector3::new([0.0, 0.0, -1.0]),
+                Vector3::new([1.0, 0.0, 0.0]),
+            ),
+        })
+    }
+
+    #[test]
+    fn tangents_follow_u_along_the_surface() {
+        let mesh = square();
+        for vertex in &mesh.vertices {
+            assert!((vertex.tangent.magnitude() - 1.0).abs() < 1e-12);
+            assert!(vertex.tangent.dot(vertex.normal).abs() < 1e-12);
+            assert!((vertex.tangent.dot(-Vector3::K) - 1.0).abs() < 1e-12);
+        }
+
+        let shape = Shape::from(mesh);
+        let ray = Ray::new(Point3D::new(0.3, 1.0, -0.6), -Vector3::J);
+        let hit = shape.intersect(&ray).unwrap();
+
+        let tangent = hit.tangent.unwrap();
+        assert!(tangent.dot(hit.normal.unwrap()).abs() < 1e-12);
+        assert!((tangent.dot(-Vector3::K) - 1.0).abs() < 1e-12);
+        assert!((hit.tex_coord.unwrap().uv.x() - 0.6).abs() < 1e-12);
+    }
+}
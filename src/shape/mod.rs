@@ -8,6 +8,7 @@ use crate::{
     material::{Material, TextureCoord, Textured},
     math::{Matrix4, Point3D, Vector3},
     ray::Ray,
+    scene::ShapeId,
 };
 
 use self::mesh::TriangleMesh;
@@ -25,6 +26,10 @@ pub struct Intersection<'a> {
     pub location: Option<Point3D>,
     pub normal: Option<Vector3>,
     pub tex_coord: Option<TextureCoord>,
+    pub tangent: Option<Vector3>,
+    pub derivatives: Option<(Vector3, Vector3)>,
+    /// Which of the scene's shapes was hit, filled in by the scene.
+    pub shape: Option<ShapeId>,
 }
 
 impl<'a> Intersection<'a> {
@@ -35,6 +40,9 @@ impl<'a> Intersection<'a> {
             location: None,
             normal: None,
             tex_coord: None,
+            tangent: None,
+            derivatives: None,
+            shape: None,
         }
     }
 
@@ -52,6 +60,16 @@ impl<'a> Intersection<'a> {
         self.tex_coord = Some(tex);
         self
     }
+
+    pub fn tangent(mut self, tangent: Vector3) -> Self {
+        self.tangent = Some(tangent);
+        self
+    }
+
+    pub fn derivatives(mut self, dpdu: Vector3, dpdv: Vector3) -> Self {
+        self.derivatives = Some((dpdu, dpdv));
+        self
+    }
 }
 
 impl<'a> PartialOrd for Intersection<'a> {
@@ -110,11 +128,53 @@ pub trait Transformable {
     }
 }
 
+/// A point on the surface of a shape, chosen uniformly by area.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurfaceSample {
+    pub point: Point3D,
+    pub normal: Vector3,
+}
+
+/// Shapes that can be sampled by area, which lets emissive shapes act as lights.
+pub trait Sampleable {
+    fn area(&self) -> f64;
+
+    /// Map the uniform random numbers `u` and `v` in [0, 1) to a point on the
+    /// surface. Shapes with an unbounded surface return `None`.
+    fn sample_surface(&self, u: f64, v: f64) -> Option<SurfaceSample>;
+
+    /// Pick a point on the surface as seen from `from`, along with the
+    /// probability density of its direction from `from` per unit of solid
+    /// angle. Shapes that can do better than picking by area override this.
+    fn sample_towards(&self, from: &Point3D, u: f64, v: f64) -> Option<(SurfaceSample, f64)> {
+        let sample = self.sample_surface(u, v)?;
+        let to_sample = sample.point - from;
+        let r2 = to_sample.norm();
+        let cos = sample.normal.dot(to_sample.normalize()).abs();
+        if r2 < 1e-12 || cos < 1e-12 {
+            return None;
+        }
+
+        Some((sample, r2 / (cos * self.area())))
+    }
+}
+
 pub trait Intersect {
     // so this is a litle ugly, but since the intersection needs to ref the parent
     // shape, we need to pass a reference down
     fn intersect<'a>(&self, ray: &Ray, shape_ref: &'a Shape) -> Option<Intersection<'a>>;
     fn normal_at(&self, point: &Point3D) -> Option<Vector3>;
+
+    /// The direction in which the `u` texture coordinate increases at `point`.
+    fn tangent_at(&self, _point: &Point3D) -> Option<Vector3> {
+        None
+    }
+
+    /// How far `point` moves per unit of `u` and of `v`, used to work out
+    /// how much of a texture a pixel covers.
+    fn uv_derivatives(&self, _point: &Point3D) -> Option<(Vector3, Vector3)> {
+        None
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -154,6 +214,24 @@ impl Shape {
         }
     }
 
+    pub fn tangent_at(&self, point: &Point3D) -> Option<Vector3> {
+        match self {
+            Shape::Sphere(ref s) => s.tangent_at(point),
+            Shape::Plane(ref s) => s.tangent_at(point),
+            Shape::Triangle(ref s) => s.tangent_at(point),
+            Shape::TriangleMesh(ref s) => s.tangent_at(point),
+        }
+    }
+
+    pub fn uv_derivatives(&self, point: &Point3D) -> Option<(Vector3, Vector3)> {
+        match self {
+            Shape::Sphere(ref s) => s.uv_derivatives(point),
+            Shape::Plane(ref s) => s.uv_derivatives(point),
+            Shape::Triangle(ref s) => s.uv_derivatives(point),
+            Shape::TriangleMesh(ref s) => s.uv_derivatives(point),
+        }
+    }
+
     pub fn texture_coord(&self, point: &Point3D) -> TextureCoord {
         match self {
             Shape::Sphere(ref s) => s.texture_coord(point),
@@ -164,6 +242,35 @@ impl Shape {
     }
 }
 
+impl Sampleable for Shape {
+    fn area(&self) -> f64 {
+        match self {
+            Shape::Sphere(s) => s.area(),
+            Shape::Plane(s) => s.area(),
+            Shape::Triangle(s) => s.area(),
+            Shape::TriangleMesh(s) => s.area(),
+        }
+    }
+
+    fn sample_surface(&self, u: f64, v: f64) -> Option<SurfaceSample> {
+        match self {
+            Shape::Sphere(s) => s.sample_surface(u, v),
+            Shape::Plane(s) => s.sample_surface(u, v),
+            Shape::Triangle(s) => s.sample_surface(u, v),
+            Shape::TriangleMesh(s) => s.sample_surface(u, v),
+        }
+    }
+
+    fn sample_towards(&self, from: &Point3D, u: f64, v: f64) -> Option<(SurfaceSample, f64)> {
+        match self {
+            Shape::Sphere(s) => s.sample_towards(from, u, v),
+            Shape::Plane(s) => s.sample_towards(from, u, v),
+            Shape::Triangle(s) => s.sample_towards(from, u, v),
+            Shape::TriangleMesh(s) => s.sample_towards(from, u, v),
+        }
+    }
+}
+
 impl Transformable for Shape {
     fn world_to_object(&self) -> Option<&Matrix4> {
         match self {
@@ -5,7 +5,7 @@ use crate::{
     shape::{Intersect, Shape},
 };
 
-use super::{Intersection, Transformable};
+use super::{Intersection, Sampleable, SurfaceSample, Transformable};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Plane {
@@ -64,6 +64,14 @@ impl Intersect for Plane {
     fn normal_at(&self, _point: &Point3D) -> Option<Vector3> {
         Some(self.normal)
     }
+
+    fn tangent_at(&self, _point: &Point3D) -> Option<Vector3> {
+        Some(self.tex_x)
+    }
+
+    fn uv_derivatives(&self, _point: &Point3D) -> Option<(Vector3, Vector3)> {
+        Some((self.tex_x, self.tex_y))
+    }
 }
 
 impl Textured for Plane {
@@ -76,6 +84,16 @@ impl Textured for Plane {
     }
 }
 
+impl Sampleable for Plane {
+    fn area(&self) -> f64 {
+        f64::INFINITY
+    }
+
+    fn sample_surface(&self, _u: f64, _v: f64) -> Option<SurfaceSample> {
+        None
+    }
+}
+
 impl Transformable for Plane {}
 
 impl From<Plane> for Shape {
@@ -83,3 +101,30 @@ impl From<Plane> for Shape {
         Shape::Plane(p)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tangents_follow_u_along_the_surface() {
+        let normals = [
+            Vector3::J,
+            Vector3::K,
+            Vector3::new([1.0, -2.0, 0.5]).normalize(),
+        ];
+
+        for &normal in normals.iter() {
+            let plane = Plane::new(Point3D::new(1.0, -1.0, 2.0), normal);
+            let point = Point3D::new(3.0, 0.5, -1.0);
+            let tangent = plane.tangent_at(&point).unwrap();
+
+            assert!((tangent.magnitude() - 1.0).abs() < 1e-12);
+            assert!(tangent.dot(normal).abs() < 1e-12);
+
+            let u = plane.texture_coord(&point).uv.x();
+            let ahead = plane.texture_coord(&(point + tangent * 0.5)).uv.x();
+            assert!((ahead - u - 0.5).abs() < 1e-12);
+        }
+    }
+}
@@ -7,7 +7,7 @@ use crate::{
     shape::{Intersect, Shape},
 };
 
-use super::{Intersection, Transformable};
+use super::{Intersection, Sampleable, SurfaceSample, Transformable};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Sphere {
@@ -63,6 +63,32 @@ impl Intersect for Sphere {
     fn normal_at(&self, point: &Point3D) -> Option<Vector3> {
         Some((point - self.center).normalize())
     }
+
+    fn tangent_at(&self, point: &Point3D) -> Option<Vector3> {
+        // u follows the angle around the y axis
+        let v = point - self.center;
+        let tangent = Vector3::new([-v.z(), 0.0, v.x()]);
+
+        // there's no sensible direction at the poles
+        if tangent.magnitude() < 1e-10_f64 {
+            return None;
+        }
+
+        Some(tangent.normalize())
+    }
+
+    fn uv_derivatives(&self, point: &Point3D) -> Option<(Vector3, Vector3)> {
+        let v = point - self.center;
+        let rho = (v.x() * v.x() + v.z() * v.z()).sqrt();
+        if rho < 1e-10_f64 {
+            return None;
+        }
+
+        // u = φ / 2π around the y axis, v = θ / π down from the north pole
+        let dpdu = Vector3::new([-v.z(), 0.0, v.x()]) * (2.0 * PI);
+        let dpdv = Vector3::new([v.y() * v.x() / rho, -rho, v.y() * v.z() / rho]) * PI;
+        Some((dpdu, dpdv))
+    }
 }
 
 impl Textured for Sphere {
@@ -78,6 +104,70 @@ impl Textured for Sphere {
     }
 }
 
+impl Sampleable for Sphere {
+    fn area(&self) -> f64 {
+        4.0 * PI * self.radius * self.radius
+    }
+
+    fn sample_surface(&self, u: f64, v: f64) -> Option<SurfaceSample> {
+        let z = 1.0 - 2.0 * u;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        let normal = Vector3::new([r * phi.cos(), r * phi.sin(), z]);
+
+        Some(SurfaceSample {
+            point: self.center + normal * self.radius,
+            normal,
+        })
+    }
+
+    /// Only the cap facing `from` can be seen from it, so pick directions
+    /// uniformly within the cone the sphere fills instead.
+    fn sample_towards(&self, from: &Point3D, u: f64, v: f64) -> Option<(SurfaceSample, f64)> {
+        let axis = self.center - from;
+        let d = axis.magnitude();
+        if d <= self.radius {
+            return None;
+        }
+
+        let axis = axis / d;
+        let sin2_max = (self.radius / d).powi(2);
+        let cos_max = (1.0 - sin2_max).sqrt();
+        // 1 - cos_max, without cancelling for small or distant spheres
+        let cone = sin2_max / (1.0 + cos_max);
+
+        let cos_theta = 1.0 - u * cone;
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+
+        let helper = if axis.x().abs() > 0.9 {
+            Vector3::J
+        } else {
+            Vector3::I
+        };
+        let x = axis.cross(helper).normalize();
+        let y = axis.cross(x);
+        let direction =
+            x * (sin_theta * phi.cos()) + y * (sin_theta * phi.sin()) + axis * cos_theta;
+
+        // the near side of the sphere along that direction
+        let along = d * cos_theta;
+        let t = along
+            - (self.radius * self.radius - d * d + along * along)
+                .max(0.0)
+                .sqrt();
+        let point = from + direction * t;
+
+        Some((
+            SurfaceSample {
+                point,
+                normal: (point - self.center) / self.radius,
+            },
+            1.0 / (2.0 * PI * cone),
+        ))
+    }
+}
+
 impl Transformable for Sphere {}
 
 impl From<Sphere> for Shape {
@@ -85,3 +175,66 @@ impl From<Sphere> for Shape {
         Shape::Sphere(s)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surface_samples_lie_on_the_sphere() {
+        let sphere = Sphere::new(Point3D::new(1.0, 2.0, 3.0), 2.0);
+        assert!((sphere.area() - 16.0 * PI).abs() < 1e-9);
+
+        for i in 0..50 {
+            let (u, v) = (i as f64 / 50.0, (i * 7 % 50) as f64 / 50.0);
+            let sample = sphere.sample_surface(u, v).unwrap();
+            assert!((sample.point.dist(&sphere.center) - 2.0).abs() < 1e-9);
+            assert!((sample.normal.magnitude() - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn samples_towards_a_point_stay_on_the_visible_cap() {
+        let sphere = Sphere::new(Point3D::new(0.0, 5.0, 0.0), 1.0);
+        let from = Point3D::default();
+        let cone = 2.0 * PI * (1.0 - (1.0 - 1.0 / 25.0_f64).sqrt());
+
+        for i in 0..50 {
+            let (u, v) = (i as f64 / 50.0, (i * 7 % 50) as f64 / 50.0);
+            let (sample, pdf) = sphere.sample_towards(&from, u, v).unwrap();
+
+            assert!((sample.point.dist(&sphere.center) - 1.0).abs() < 1e-9);
+            assert!(sample.normal.dot((from - sample.point).normalize()) >= -1e-9);
+            assert!((1.0 / pdf - cone).abs() < 1e-9);
+        }
+
+        assert!(sphere
+            .sample_towards(&Point3D::new(0.0, 5.5, 0.0), 0.5, 0.5)
+            .is_none());
+    }
+
+    #[test]
+    fn tangents_follow_u_along_the_surface() {
+        let sphere = Sphere::new(Point3D::new(1.0, 2.0, 3.0), 2.0);
+
+        for &(theta, phi) in [(0.4_f64, 0.3_f64), (1.2, 2.0), (2.5, -1.0), (1.6, -2.8)].iter() {
+            let offset = Vector3::new([
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            ]) * 2.0;
+            let point = sphere.center + offset;
+            let normal = sphere.normal_at(&point).unwrap();
+            let tangent = sphere.tangent_at(&point).unwrap();
+
+            assert!((tangent.magnitude() - 1.0).abs() < 1e-9);
+            assert!(tangent.dot(normal).abs() < 1e-9);
+
+            let u = sphere.texture_coord(&point).uv.x();
+            let ahead = sphere.texture_coord(&(point + tangent * 1e-4)).uv.x();
+            assert!(ahead > u);
+        }
+
+        assert!(sphere.tangent_at(&Point3D::new(1.0, 4.0, 3.0)).is_none());
+    }
+}
@@ -4,7 +4,7 @@ use crate::{
     ray::Ray,
 };
 
-use super::{Intersect, Intersection, Shape, Transformable};
+use super::{Intersect, Intersection, Sampleable, Shape, SurfaceSample, Transformable};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Triangle {
@@ -45,6 +45,15 @@ impl Intersect for Triangle {
     fn normal_at(&self, _point: &Point3D) -> Option<Vector3> {
         Some(self.raw_normal.normalize())
     }
+
+    fn tangent_at(&self, _point: &Point3D) -> Option<Vector3> {
+        // u is the barycentric weight of p0
+        Some((self.p0 - self.p2).normalize())
+    }
+
+    fn uv_derivatives(&self, _point: &Point3D) -> Option<(Vector3, Vector3)> {
+        Some((self.p0 - self.p2, self.p1 - self.p2))
+    }
 }
 
 impl Textured for Triangle {
@@ -64,6 +73,20 @@ impl Textured for Triangle {
     }
 }
 
+impl Sampleable for Triangle {
+    fn area(&self) -> f64 {
+        self.raw_normal.magnitude() / 2.0
+    }
+
+    fn sample_surface(&self, u: f64, v: f64) -> Option<SurfaceSample> {
+        let (b0, b1) = uniform_barycentric(u, v);
+        Some(SurfaceSample {
+            point: self.p0 + (self.p1 - self.p0) * b0 + (self.p2 - self.p0) * b1,
+            normal: self.raw_normal.normalize(),
+        })
+    }
+}
+
 impl Transformable for Triangle {}
 
 impl From<Triangle> for Shape {
@@ -72,6 +95,13 @@ impl From<Triangle> for Shape {
     }
 }
 
+/// Map two uniform random numbers to uniformly distributed barycentric
+/// coordinates (the weights of the second and third vertex).
+pub fn uniform_barycentric(u: f64, v: f64) -> (f64, f64) {
+    let su = u.sqrt();
+    (su * (1.0 - v), su * v)
+}
+
 pub fn triangle_intersect(
     p0: &Point3D,
     p1: &Point3D,
@@ -122,3 +152,48 @@ pub fn pre_calc_traingle_intersect(
 
     Some((d, Point2D::new(u, v)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surface_samples_lie_inside_the_triangle() {
+        let triangle = Triangle::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(2.0, 0.0, 0.0),
+            Point3D::new(0.0, 0.0, 3.0),
+        );
+        assert!((triangle.area() - 3.0).abs() < 1e-12);
+
+        for i in 0..50 {
+            let (u, v) = (i as f64 / 50.0, (i * 7 % 50) as f64 / 50.0);
+            let sample = triangle.sample_surface(u, v).unwrap();
+            let p = sample.point;
+
+            assert!(p.y().abs() < 1e-12);
+            assert!(p.x() >= -1e-12 && p.z() >= -1e-12);
+            assert!(p.x() / 2.0 + p.z() / 3.0 <= 1.0 + 1e-12);
+            assert!((sample.normal.magnitude() - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn tangents_follow_u_along_the_surface() {
+        let triangle = Triangle::new(
+            Point3D::new(1.0, 0.0, 0.0),
+            Point3D::new(0.0, 2.0, 1.0),
+            Point3D::new(-1.0, 0.5, 2.0),
+        );
+        let point = Point3D::new(0.0, 2.5 / 3.0, 1.0);
+        let normal = triangle.normal_at(&point).unwrap();
+        let tangent = triangle.tangent_at(&point).unwrap();
+
+        assert!((tangent.magnitude() - 1.0).abs() < 1e-12);
+        assert!(tangent.dot(normal).abs() < 1e-12);
+
+        let u = triangle.texture_coord(&point).uv.x();
+        let ahead = triangle.texture_coord(&(point + tangent * 1e-3)).uv.x();
+        assert!(ahead > u);
+    }
+}
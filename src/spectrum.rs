@@ -0,0 +1,313 @@
+use crate::color::Color;
+
+/// The shortest wavelength (in nm) considered when working with spectra.
+pub const MIN_WAVELENGTH: f64 = 380.0;
+/// The longest wavelength (in nm) considered when working with spectra.
+pub const MAX_WAVELENGTH: f64 = 780.0;
+
+fn lobe(x: f64, mu: f64, sigma_low: f64, sigma_high: f64) -> f64 {
+    let sigma = if x < mu { sigma_low } else { sigma_high };
+    let t = (x - mu) / sigma;
+    (-0.5 * t * t).exp()
+}
+
+/// The CIE 1931 standard observer color matching functions at `wavelength`
+/// (in nm), using the multi-lobe fit from Wyman, Sloan and Shirley (2013).
+pub fn cie_xyz(wavelength: f64) -> (f64, f64, f64) {
+    let l = wavelength;
+    let x = 1.056 * lobe(l, 599.8, 37.9, 31.0) + 0.362 * lobe(l, 442.0, 16.0, 26.7)
+        - 0.065 * lobe(l, 501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(l, 568.8, 46.9, 40.5) + 0.286 * lobe(l, 530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(l, 437.0, 11.8, 36.0) + 0.681 * lobe(l, 459.0, 26.0, 13.8);
+
+    (x, y, z)
+}
+
+/// Convert CIE XYZ to linear sRGB (D65 white point).
+pub fn xyz_to_rgb(x: f64, y: f64, z: f64) -> Color {
+    Color::new(
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    )
+}
+
+/// The (unnormalized, possibly out of gamut) linear sRGB color of a single
+/// wavelength.
+pub fn wavelength_to_rgb(wavelength: f64) -> Color {
+    let (x, y, z) = cie_xyz(wavelength);
+    xyz_to_rgb(x, y, z)
+}
+
+/// Integrate `spectrum` against the color matching functions, yielding its
+/// CIE XYZ coordinates.
+fn integrate<F>(spectrum: F) -> (f64, f64, f64)
+where
+    F: Fn(f64) -> f64,
+{
+    let step = 1.0;
+    let mut xyz = (0.0, 0.0, 0.0);
+    let mut wavelength = MIN_WAVELENGTH + step / 2.0;
+
+    while wavelength < MAX_WAVELENGTH {
+        let power = spectrum(wavelength) * step;
+        let (x, y, z) = cie_xyz(wavelength);
+        xyz = (xyz.0 + x * power, xyz.1 + y * power, xyz.2 + z * power);
+        wavelength += step;
+    }
+
+    xyz
+}
+
+/// The linear sRGB color of the equal energy spectrum, which we treat as white.
+fn equal_energy_rgb() -> Color {
+    let (x, y, z) = integrate(|_| 1.0);
+    xyz_to_rgb(x, y, z)
+}
+
+fn white_balance(color: Color, white: &Color) -> Color {
+    Color::new(color.r / white.r, color.g / white.g, color.b / white.b)
+}
+
+/// Upsample a linear RGB color to a smooth reflectance spectrum and evaluate it
+/// at `wavelength`. The basis functions sum to one everywhere, so white maps to
+/// a flat spectrum and reflectances stay within [0, 1].
+pub fn rgb_to_spectrum(color: &Color, wavelength: f64) -> f64 {
+    let blue = 1.0 - smoothstep(485.0, 505.0, wavelength);
+    let red = smoothstep(575.0, 595.0, wavelength);
+    let green = 1.0 - blue - red;
+
+    color.r * red + color.g * green + color.b * blue
+}
+
+fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Planck's law: spectral radiance of a black body at `temperature` kelvin.
+fn planck(wavelength: f64, temperature: f64) -> f64 {
+    const H: f64 = 6.62607015e-34;
+    const C: f64 = 299_792_458.0;
+    const K: f64 = 1.380649e-23;
+
+    let l = wavelength * 1e-9;
+    2.0 * H * C * C / (l.powi(5) * ((H * C / (l * K * temperature)).exp() - 1.0))
+}
+
+/// CIE standard illuminant D65, 380nm to 780nm in 10nm steps.
+const D65: [f64; 41] = [
+    49.98, 54.65, 82.75, 91.49, 93.43, 86.68, 104.86, 117.01, 117.81, 114.86, 115.92, 108.81,
+    109.35, 107.80, 104.79, 107.69, 104.41, 104.05, 100.00, 96.33, 95.79, 88.69, 90.01, 89.60,
+    87.70, 83.29, 83.70, 80.03, 80.21, 82.28, 78.28, 69.72, 71.61, 74.35, 61.60, 69.89, 75.09,
+    63.59, 46.42, 66.81, 63.38,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StandardIlluminant {
+    /// Incandescent tungsten light.
+    A,
+    /// Average noon daylight.
+    D65,
+    /// The equal energy spectrum.
+    E,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SpectrumKind {
+    Blackbody(f64),
+    Illuminant(StandardIlluminant),
+}
+
+/// The emission spectrum of a light source. Spectra are normalized to the
+/// luminance of the equal energy spectrum, so switching a light to a spectrum
+/// changes its tint but not its brightness.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spectrum {
+    kind: SpectrumKind,
+    scale: f64,
+    color: Color,
+}
+
+impl Spectrum {
+    /// The spectrum of an ideal black body at `temperature` kelvin.
+    pub fn blackbody(temperature: f64) -> Self {
+        Self::from_kind(SpectrumKind::Blackbody(temperature))
+    }
+
+    pub fn illuminant(illuminant: StandardIlluminant) -> Self {
+        Self::from_kind(SpectrumKind::Illuminant(illuminant))
+    }
+
+    fn from_kind(kind: SpectrumKind) -> Self {
+        let mut spectrum = Spectrum {
+            kind,
+            scale: 1.0,
+            color: Color::WHITE,
+        };
+
+        let (_, white_y, _) = integrate(|_| 1.0);
+        let (_, y, _) = integrate(|l| spectrum.raw(l));
+        spectrum.scale = white_y / y;
+
+        let (x, y, z) = integrate(|l| spectrum.value(l));
+        spectrum.color = white_balance(xyz_to_rgb(x, y, z), &equal_energy_rgb());
+        spectrum
+    }
+
+    fn raw(&self, wavelength: f64) -> f64 {
+        match self.kind {
+            SpectrumKind::Blackbody(temperature) => planck(wavelength, temperature),
+            SpectrumKind::Illuminant(StandardIlluminant::A) => planck(wavelength, 2856.0),
+            SpectrumKind::Illuminant(StandardIlluminant::D65) => {
+                let pos = ((wavelength - MIN_WAVELENGTH) / 10.0).clamp(0.0, 40.0);
+                let i = (pos as usize).min(39);
+                let t = pos - i as f64;
+                D65[i] * (1.0 - t) + D65[i + 1] * t
+            }
+            SpectrumKind::Illuminant(StandardIlluminant::E) => 1.0,
+        }
+    }
+
+    /// The relative power of this spectrum at `wavelength` (in nm).
+    pub fn value(&self, wavelength: f64) -> f64 {
+        self.raw(wavelength) * self.scale
+    }
+
+    /// This spectrum as seen by an RGB renderer.
+    pub fn color(&self) -> Color {
+        self.color
+    }
+}
+
+/// Converts radiance measured at individual wavelengths back to RGB when
+/// writing the framebuffer, by way of CIE XYZ.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpectralFilm {
+    samples: usize,
+    white: Color,
+}
+
+impl SpectralFilm {
+    pub fn new(samples: usize) -> Self {
+        Self {
+            samples: samples.max(1),
+            white: equal_energy_rgb(),
+        }
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// The wavelength to trace for `stratum` (of [samples](Self::samples)),
+    /// offset within the stratum by `jitter` in [0, 1).
+    pub fn wavelength(&self, stratum: usize, jitter: f64) -> f64 {
+        let step = (MAX_WAVELENGTH - MIN_WAVELENGTH) / self.samples as f64;
+        MIN_WAVELENGTH + (stratum as f64 + jitter) * step
+    }
+
+    /// Resolve `(wavelength, radiance)` pairs, one per stratum, to linear RGB.
+    pub fn develop(&self, radiance: &[(f64, f64)]) -> Color {
+        let step = (MAX_WAVELENGTH - MIN_WAVELENGTH) / self.samples as f64;
+        let (x, y, z) = radiance
+            .iter()
+            .fold((0.0, 0.0, 0.0), |acc, (wavelength, power)| {
+                let (x, y, z) = cie_xyz(*wavelength);
+                let power = power * step;
+                (acc.0 + x * power, acc.1 + y * power, acc.2 + z * power)
+            });
+
+        white_balance(xyz_to_rgb(x, y, z), &self.white)
+    }
+}
+
+/// Pick `count` evenly spaced visible wavelengths along with the color each one
+/// contributes. The weights are clamped to the sRGB gamut and normalized so
+/// they sum to white, which means tracing the same scene once per wavelength
+/// and summing `radiance * weight` reproduces the plain RGB result. At least
+/// three wavelengths are used, so each channel has one to carry it.
+pub fn wavelength_samples(count: usize) -> Vec<(f64, Color)> {
+    let count = count.max(3);
+
+    // the ends of the visible range contribute next to nothing, so keep the
+    // samples where they matter
+    let (low, high) = (400.0, 700.0);
+    let step = (high - low) / count as f64;
+
+    let samples = (0..count)
+        .map(|i| {
+            let wavelength = low + (i as f64 + 0.5) * step;
+            let rgb = wavelength_to_rgb(wavelength);
+            (
+                wavelength,
+                Color::new(rgb.r.max(0.0), rgb.g.max(0.0), rgb.b.max(0.0)),
+            )
+        })
+        .collect::<Vec<(f64, Color)>>();
+
+    let total = samples
+        .iter()
+        .fold(Color::BLACK, |acc, (_, weight)| acc + weight);
+
+    samples
+        .into_iter()
+        .map(|(wavelength, w)| {
+            let normalized = Color::new(w.r / total.r, w.g / total.g, w.b / total.b);
+            (wavelength, normalized)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wavelength_samples_sum_to_white() {
+        for count in [0, 1, 2, 3, 8, 16].iter() {
+            let total = wavelength_samples(*count)
+                .iter()
+                .fold(Color::BLACK, |acc, (_, weight)| acc + weight);
+
+            assert!((total.r - 1.0).abs() < 1e-9);
+            assert!((total.g - 1.0).abs() < 1e-9);
+            assert!((total.b - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn flat_spectra_are_white() {
+        let e = Spectrum::illuminant(StandardIlluminant::E).color();
+        assert!((e.r - 1.0).abs() < 1e-9);
+        assert!((e.g - 1.0).abs() < 1e-9);
+        assert!((e.b - 1.0).abs() < 1e-9);
+
+        let film = SpectralFilm::new(400);
+        let radiance = (0..film.samples())
+            .map(|i| film.wavelength(i, 0.5))
+            .map(|l| (l, rgb_to_spectrum(&Color::WHITE, l)))
+            .collect::<Vec<(f64, f64)>>();
+        let white = film.develop(&radiance);
+        assert!((white.r - 1.0).abs() < 1e-3);
+        assert!((white.g - 1.0).abs() < 1e-3);
+        assert!((white.b - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn low_temperatures_are_red() {
+        let candle = Spectrum::blackbody(1900.0).color();
+        let sky = Spectrum::blackbody(10000.0).color();
+        assert!(candle.r > candle.b);
+        assert!(sky.b > sky.r);
+    }
+
+    #[test]
+    fn matching_functions_peak_where_expected() {
+        let (_, y_green, _) = cie_xyz(555.0);
+        let (_, y_blue, z_blue) = cie_xyz(450.0);
+
+        assert!(y_green > 0.95);
+        assert!(z_blue > y_blue);
+    }
+}